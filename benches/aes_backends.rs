@@ -0,0 +1,37 @@
+//! Throughput of the AES backends, run with `cargo bench`.
+//!
+//! The table backend is the one to attack with cache timing; the constant-time
+//! backend shows what closing that channel costs.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use cryptopals::block_cipher::{ecb_encrypt, Aes, AesBackend};
+use cryptopals::utils::encrypt_aes_128;
+
+const KEY: &[u8] = b"YELLOW SUBMARINE";
+const INPUT_SIZE: usize = 1 << 16;
+const ITERATIONS: u32 = 20;
+
+fn bench(name: &str, mut f: impl FnMut(&[u8]) -> Vec<u8>) {
+    let input = vec![0x42; INPUT_SIZE];
+    // warm up caches and the allocator before timing
+    black_box(f(&input));
+
+    let mut best = Duration::MAX;
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        black_box(f(black_box(&input)));
+        best = best.min(start.elapsed());
+    }
+    let throughput = INPUT_SIZE as f64 / best.as_secs_f64() / (1024. * 1024.);
+    println!("{:<28} {:>10.2?} per 64KiB  {:>8.2} MiB/s", name, best, throughput);
+}
+
+fn main() {
+    bench("utils::encrypt_aes_128", |input| encrypt_aes_128(input, KEY));
+    for backend in [AesBackend::Crate, AesBackend::Table, AesBackend::ConstantTime] {
+        let cipher = Aes::with_backend(KEY, backend);
+        bench(&format!("Aes {:?}", backend), |input| ecb_encrypt(&cipher, input));
+    }
+}
@@ -1,12 +1,18 @@
-use std::{collections::HashMap, error::Error, str::from_utf8};
-
 use crate::{
-    challenge_17::Challenge17,
-    oracle::{Oracle, StaticOracle},
-    utils::Xor,
-    utils::{DetectDuplicate, Hex},
+    gf128::{ghash, Gf128},
+    gf128_poly::{roots, Poly},
+    oracle::{EditOracle, KeyAsIvOracle, Oracle, PaddingOracle, TimingOracle},
+    pkcs7::{self, strip_padding},
+    scoring::{CaseInsensitive, CharacterTable, Scorer},
+    utils::{random_key, DetectDuplicate},
+    utils::{Base64, Hex, RepeatingKeyXor, Xor},
 };
-use log::{info, warn};
+use log::info;
+use std::collections::HashMap;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::time::Duration;
 
 pub fn score_character(char: &char) -> u32 {
     match char {
@@ -43,7 +49,7 @@ pub fn score_character(char: &char) -> u32 {
 
 pub struct DecryptCipherResult {
     pub key: u8,
-    pub score: u32,
+    pub score: f64,
     pub decoded: Vec<u8>,
 }
 
@@ -51,7 +57,7 @@ impl DecryptCipherResult {
     pub fn new() -> Self {
         DecryptCipherResult {
             key: 0,
-            score: 0,
+            score: f64::NEG_INFINITY,
             decoded: Vec::new(),
         }
     }
@@ -70,27 +76,114 @@ impl Default for DecryptCipherResult {
     }
 }
 
-fn score_character_frequency(input: &[u8]) -> u32 {
-    input
-        .iter()
-        .fold(0, |acc, byte| acc + score_character(&(*byte as char)))
-}
-
-pub fn attack_single_character_xor(ciphertext: Vec<u8>) -> DecryptCipherResult {
+pub fn attack_single_character_xor(
+    ciphertext: Vec<u8>,
+    scorer: &impl Scorer,
+) -> DecryptCipherResult {
     (0..=255).fold(DecryptCipherResult::new(), |acc, key| {
         let ciphertext = ciphertext.clone().xor(&key).to_owned();
-        let score = score_character_frequency(ciphertext.as_slice());
-        match score.cmp(&acc.score) {
-            std::cmp::Ordering::Greater => DecryptCipherResult {
+        let score = scorer.score(ciphertext.as_slice());
+        if score > acc.score {
+            DecryptCipherResult {
                 key,
                 score,
                 decoded: ciphertext,
-            },
-            _ => acc,
+            }
+        } else {
+            acc
         }
     })
 }
 
+pub struct SingleByteXorCandidate {
+    /// 1-based line (or item) number the ciphertext came from.
+    pub line: usize,
+    pub key: u8,
+    /// Score per byte, so ciphertexts of different lengths compare fairly.
+    pub score: f64,
+    pub plaintext: Vec<u8>,
+}
+
+/// Breaks every ciphertext as single-byte XOR, spread over all available
+/// threads, and returns the `top_k` most plausible decryptions best first.
+pub fn detect_single_character_xor<S: Scorer + Sync>(
+    ciphertexts: impl IntoIterator<Item = Vec<u8>>,
+    top_k: usize,
+    scorer: &S,
+) -> Vec<SingleByteXorCandidate> {
+    let ciphertexts = ciphertexts.into_iter().collect::<Vec<_>>();
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = ciphertexts.len().div_ceil(threads).max(1);
+
+    let mut candidates = std::thread::scope(|scope| {
+        let handles = ciphertexts
+            .chunks(chunk_size)
+            .enumerate()
+            .map(|(chunk, ciphertexts)| {
+                scope.spawn(move || {
+                    ciphertexts
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, ciphertext)| !ciphertext.is_empty())
+                        .map(|(i, ciphertext)| {
+                            let result = attack_single_character_xor(ciphertext.clone(), scorer);
+                            SingleByteXorCandidate {
+                                line: chunk * chunk_size + i + 1,
+                                key: result.key,
+                                score: result.score / ciphertext.len() as f64,
+                                plaintext: result.decoded,
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("Detector thread panicked"))
+            .collect::<Vec<_>>()
+    });
+
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.line.cmp(&b.line)));
+    candidates.truncate(top_k);
+    candidates
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineEncoding {
+    Hex,
+    Base64,
+}
+
+/// Runs [`detect_single_character_xor`] over a file with one encoded ciphertext
+/// per line. Blank lines are skipped but still counted, and a line that fails to
+/// decode is reported as `InvalidData`.
+pub fn detect_single_character_xor_in_file<S: Scorer + Sync>(
+    path: impl AsRef<Path>,
+    encoding: LineEncoding,
+    top_k: usize,
+    scorer: &S,
+) -> io::Result<Vec<SingleByteXorCandidate>> {
+    let input = std::fs::read_to_string(path)?;
+    let ciphertexts = input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let decoded = match encoding {
+                LineEncoding::Hex => Vec::<u8>::try_from_hex(line.trim()),
+                LineEncoding::Base64 => Vec::<u8>::try_from_base64(line.trim()),
+            };
+            decoded.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {} is not valid {:?}", i + 1, encoding),
+                )
+            })
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    Ok(detect_single_character_xor(ciphertexts, top_k, scorer))
+}
+
 fn hamming_distance(a: &[u8], b: &[u8]) -> u32 {
     a.iter()
         .zip(b.iter())
@@ -100,7 +193,7 @@ fn hamming_distance(a: &[u8], b: &[u8]) -> u32 {
 pub fn keysize_edit_distance(ciphertext: &[u8], keysize: usize) -> f32 {
     let max_chunks = ciphertext.len() / keysize;
     // take 16 keysize chunks to compare
-    let mut chunks = ciphertext[0..(keysize * 16).min(max_chunks)].chunks(keysize);
+    let mut chunks = ciphertext[0..keysize * max_chunks.min(16)].chunks(keysize);
     let mut total_distance = 0.;
     let mut total_comparisons = 0;
     while let (Some(a), Some(b)) = (chunks.next(), chunks.next()) {
@@ -110,171 +203,1152 @@ pub fn keysize_edit_distance(ciphertext: &[u8], keysize: usize) -> f32 {
     total_distance / total_comparisons as f32
 }
 
-pub fn attack_repeating_key_xor(ciphertext: &[u8]) -> Vec<u8> {
-    let (keysize, _score) = (2..40)
-        .map(|keysize| (keysize, keysize_edit_distance(ciphertext, keysize)))
-        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
-        .unwrap();
+/// Average index of coincidence of the columns `keysize` apart. Columns that
+/// share a key byte keep the skewed distribution of the plaintext.
+fn keysize_coincidence(ciphertext: &[u8], keysize: usize) -> f64 {
+    let columns = transpose(ciphertext, keysize);
+    columns
+        .iter()
+        .map(|column| {
+            let mut counts = [0usize; 256];
+            column.iter().for_each(|&byte| counts[byte as usize] += 1);
+            let n = column.len();
+            if n < 2 {
+                return 0.;
+            }
+            let pairs = counts.iter().map(|c| c * c.saturating_sub(1)).sum::<usize>();
+            pairs as f64 / (n * (n - 1)) as f64
+        })
+        .sum::<f64>()
+        / keysize as f64
+}
 
-    info!("Keysize: {}", keysize);
+/// Distances between consecutive repeats of every trigram in the ciphertext;
+/// repeated plaintext under the same key bytes repeats a multiple of the key
+/// size apart.
+fn kasiski_distances(ciphertext: &[u8]) -> Vec<usize> {
+    let mut last_seen = HashMap::new();
+    let mut distances = vec![];
+    for (i, trigram) in ciphertext.windows(3).enumerate() {
+        if let Some(previous) = last_seen.insert(trigram, i) {
+            distances.push(i - previous);
+        }
+    }
+    distances
+}
 
+fn transpose(ciphertext: &[u8], keysize: usize) -> Vec<Vec<u8>> {
     let mut transposed_blocks = vec![Vec::new(); keysize];
     for (i, val) in ciphertext.iter().enumerate() {
         transposed_blocks[i % keysize].push(*val);
     }
-
     transposed_blocks
-        .into_iter()
-        .map(|block| attack_single_character_xor(block).key)
-        .collect::<Vec<_>>()
-}
-
-pub fn get_prefix_length(block_size: usize, oracle: &impl Oracle) -> usize {
-    let mut prefix_padding = 0;
-    let test_string = "B".repeat(block_size * 2);
-    while prefix_padding <= block_size {
-        // if we can detect two identical blocks, then this must be the correct prefix padding
-        // step 1 block at a time
-        let mut prefix = "A".repeat(prefix_padding).as_bytes().to_vec();
-        prefix.extend_from_slice(test_string.as_bytes());
-        let cipher = oracle.encrypt(&prefix);
-        let blocks = cipher.chunks(block_size).collect::<Vec<_>>();
-        for i in 1..blocks.len() {
-            if blocks[i - 1] == blocks[i] {
-                return (i - 1) * block_size - prefix_padding;
-            }
+}
+
+/// Orders candidate key sizes best first, by summing each size's rank under
+/// normalised Hamming distance, index of coincidence and Kasiski periodicity.
+pub fn rank_keysizes(ciphertext: &[u8], keysizes: RangeInclusive<usize>) -> Vec<usize> {
+    let keysizes = keysizes
+        .filter(|&keysize| keysize > 0 && ciphertext.len() >= keysize * 2)
+        .collect::<Vec<_>>();
+    let distances = kasiski_distances(ciphertext);
+
+    // each metric is arranged so that higher is better
+    let metrics: [Box<dyn Fn(usize) -> f64>; 3] = [
+        Box::new(|keysize| -keysize_edit_distance(ciphertext, keysize) as f64),
+        Box::new(|keysize| keysize_coincidence(ciphertext, keysize)),
+        Box::new(|keysize| {
+            let periodic = distances.iter().filter(|&&d| d.is_multiple_of(keysize)).count();
+            periodic as f64 / distances.len().max(1) as f64
+        }),
+    ];
+
+    let mut rank_sums = vec![0; keysizes.len()];
+    for metric in metrics.iter() {
+        let values = keysizes.iter().map(|&keysize| metric(keysize)).collect::<Vec<_>>();
+        for (i, value) in values.iter().enumerate() {
+            rank_sums[i] += values.iter().filter(|&other| other > value).count();
         }
-        prefix_padding += 1;
     }
-    panic!("Unable to determine prefix length");
+
+    let mut ranked = keysizes.into_iter().zip(rank_sums).collect::<Vec<_>>();
+    ranked.sort_by_key(|&(keysize, rank)| (rank, keysize));
+    ranked.into_iter().map(|(keysize, _)| keysize).collect()
 }
 
-pub fn attack_ecb(oracle: impl Oracle) -> Vec<u8> {
-    let mut block_size = None;
-    // first determine the prefix length and pad it out
-    let encrypted_secret = oracle.encrypt(&[]);
+/// The shortest key that repeats to give `key`, so that a multiple of the real
+/// key size collapses back onto it.
+fn shortest_period(key: &[u8]) -> &[u8] {
+    let period = (1..key.len())
+        .find(|&p| key.len().is_multiple_of(p) && key.chunks(p).all(|chunk| chunk == &key[..p]))
+        .unwrap_or(key.len());
+    &key[..period]
+}
 
-    let mut input = "".to_string();
+pub struct RepeatingKeyCandidate {
+    pub key: Vec<u8>,
+    pub plaintext: Vec<u8>,
+    /// Share of the probability across all returned candidates, from a softmax
+    /// of each plaintext's per-byte score.
+    pub confidence: f64,
+}
 
-    let mut first_increment = None;
-    let mut tmp_length = encrypted_secret.len();
-    for i in 1..1024 {
-        input.push('A');
-        // determine block size
-        let cipher = oracle.encrypt(input.as_bytes());
-        if tmp_length != cipher.len() {
-            if let Some(_first_increment) = first_increment {
-                block_size = Some((i - _first_increment) as usize);
-                break;
-            } else {
-                tmp_length = cipher.len();
-                first_increment = Some(i);
-            }
+/// Breaks repeating-key XOR for each of the `candidates` most promising key
+/// sizes in `keysizes`, and returns the decryptions best first.
+pub fn break_repeating_key_xor(
+    ciphertext: &[u8],
+    keysizes: RangeInclusive<usize>,
+    candidates: usize,
+    scorer: &impl Scorer,
+) -> Vec<RepeatingKeyCandidate> {
+    let mut results: Vec<(Vec<u8>, Vec<u8>, f64)> = vec![];
+    for keysize in rank_keysizes(ciphertext, keysizes).into_iter().take(candidates) {
+        let key = transpose(ciphertext, keysize)
+            .into_iter()
+            .map(|block| attack_single_character_xor(block, scorer).key)
+            .collect::<Vec<_>>();
+        let key = shortest_period(&key).to_vec();
+        if results.iter().any(|(known, _, _)| *known == key) {
+            continue;
         }
+        let plaintext = ciphertext.encrypt_repeating_key_xor(&key);
+        let score = scorer.score(&plaintext) / plaintext.len().max(1) as f64;
+        results.push((key, plaintext, score));
     }
 
-    if block_size.is_none() {
-        panic!("Unable to determine block size");
+    // shorter keys win ties, since any multiple of the right size also works
+    results.sort_by(|a, b| b.2.total_cmp(&a.2).then(a.0.len().cmp(&b.0.len())));
+    let best = results.first().map_or(0., |result| result.2);
+    let total = results.iter().map(|result| (result.2 - best).exp()).sum::<f64>();
+    results
+        .into_iter()
+        .map(|(key, plaintext, score)| RepeatingKeyCandidate {
+            key,
+            plaintext,
+            confidence: (score - best).exp() / total,
+        })
+        .collect()
+}
+
+/// The most likely key of 2 to 40 bytes, or `None` if the ciphertext is too
+/// short to hold two copies of even the shortest one.
+pub fn attack_repeating_key_xor(ciphertext: &[u8], scorer: &impl Scorer) -> Option<Vec<u8>> {
+    let best = break_repeating_key_xor(ciphertext, 2..=40, 5, scorer)
+        .into_iter()
+        .next()?;
+
+    info!("Keysize: {}", best.key.len());
+    Some(best.key)
+}
+
+/// PKCS#7 can't pad to blocks any larger than this.
+const MAX_BLOCK_SIZE: usize = 255;
+
+/// Index of the first of two equal adjacent blocks.
+fn first_repeated_block(ciphertext: &[u8], block_size: usize) -> Option<usize> {
+    let blocks = ciphertext.chunks(block_size).collect::<Vec<_>>();
+    (1..blocks.len()).find(|&i| blocks[i - 1] == blocks[i]).map(|i| i - 1)
+}
+
+/// Finds the length of the fixed prefix an ECB oracle puts in front of our input,
+/// by growing a run of identical bytes until it fills two aligned blocks.
+///
+/// A prefix that ends in the fill byte, or a suffix that starts with it, extends
+/// the run and throws that probe off in one direction or the other. The probe is
+/// run with three different bytes and the answer is whatever two of them agree
+/// on; if the prefix and the suffix each fool a different probe, there is none.
+pub fn get_prefix_length(block_size: usize, oracle: &impl Oracle) -> Option<usize> {
+    let probe = |fill: u8| {
+        (0..block_size).find_map(|padding| {
+            let cipher = oracle.encrypt(&vec![fill; padding + block_size * 2]);
+            first_repeated_block(&cipher, block_size)
+                .and_then(|i| (i * block_size).checked_sub(padding))
+        })
+    };
+    let probes = [b'A', b'B', b'C'].map(probe);
+    probes
+        .iter()
+        .flatten()
+        .find(|&&length| probes.iter().filter(|&&p| p == Some(length)).count() >= 2)
+        .copied()
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EcbAttackError {
+    /// The ciphertext never grew as the input did.
+    UnknownBlockSize,
+    /// Repeated input blocks did not encrypt to repeated ciphertext blocks.
+    NotEcb,
+    /// Our input could not be lined up with a block boundary.
+    UnknownPrefixLength,
+    /// No byte value reproduced the block being attacked, e.g. because the
+    /// oracle's output is not deterministic.
+    NoMatchingByte { offset: usize },
+    /// Retrying never landed our input on a block boundary.
+    MarkerNotFound,
+    /// A cut and paste replacement must fit in one block with its padding.
+    ReplacementTooLong,
+}
+
+/// Byte-at-a-time decryption of the secret an ECB oracle appends to our input.
+pub struct EcbDecryptor<'a, O: Oracle> {
+    oracle: &'a O,
+    block_size: usize,
+    prefix_length: usize,
+    suffix_length: usize,
+}
+
+impl<'a, O: Oracle> EcbDecryptor<'a, O> {
+    /// Probes the oracle for its block size, mode and prefix length.
+    pub fn new(oracle: &'a O) -> Result<Self, EcbAttackError> {
+        // the first time the ciphertext grows the padding went from one byte to a
+        // whole block, which also gives away how much the oracle adds itself
+        let empty_length = oracle.encrypt(&[]).len();
+        let (input_length, block_size) = (1..=MAX_BLOCK_SIZE)
+            .find_map(|i| {
+                let length = oracle.encrypt(&vec![b'A'; i]).len();
+                (length != empty_length).then(|| (i, length - empty_length))
+            })
+            .ok_or(EcbAttackError::UnknownBlockSize)?;
+        let repeated = oracle.encrypt(&vec![b'X'; block_size * 3]);
+        // ciphertext that grows a byte at a time comes from a stream cipher
+        if block_size == 1 || !repeated.as_slice().contains_duplicates(block_size as u32) {
+            return Err(EcbAttackError::NotEcb);
+        }
+
+        let prefix_length =
+            get_prefix_length(block_size, oracle).ok_or(EcbAttackError::UnknownPrefixLength)?;
+        let suffix_length = empty_length
+            .checked_sub(input_length + prefix_length)
+            .ok_or(EcbAttackError::UnknownPrefixLength)?;
+
+        Ok(EcbDecryptor {
+            oracle,
+            block_size,
+            prefix_length,
+            suffix_length,
+        })
     }
 
-    let block_size = block_size.unwrap();
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
 
-    let prefix_length = get_prefix_length(block_size, &oracle);
+    pub fn prefix_length(&self) -> usize {
+        self.prefix_length
+    }
 
-    // skip forward to the next whole block starting index
-    let mut prefix_padding = block_size - (prefix_length % block_size);
-    if prefix_padding == block_size {
-        prefix_padding = 0;
+    pub fn suffix_length(&self) -> usize {
+        self.suffix_length
     }
-    let skip_length = prefix_length + prefix_padding;
 
-    let constant_prefix = "B".repeat(prefix_padding);
+    /// Recovers the suffix, stopping where its padding begins.
+    pub fn decrypt(&self) -> Result<Vec<u8>, EcbAttackError> {
+        let block_size = self.block_size;
+        // filler that completes the prefix's last block, so our blocks start at `start`
+        let alignment = (block_size - self.prefix_length % block_size) % block_size;
+        let start = self.prefix_length + alignment;
+
+        let mut known = vec![b'A'; block_size - 1];
+        while known.len() - (block_size - 1) < self.suffix_length {
+            let recovered = known.len() - (block_size - 1);
 
-    let is_ecb = oracle
-        .encrypt("X".repeat(block_size * 3).as_bytes())
-        .as_slice()
-        .contains_duplicates(block_size as u32);
+            // push the next unknown byte to the end of a block
+            let filler = vec![b'A'; alignment + block_size - 1 - recovered % block_size];
+            let target = start + recovered / block_size * block_size;
+            let encrypted = self.oracle.encrypt(&filler);
+            let target = &encrypted[target..target + block_size];
 
-    if !is_ecb {
-        panic!("Not ECB");
+            let mut probe = vec![b'A'; alignment];
+            probe.extend_from_slice(&known[known.len() - (block_size - 1)..]);
+            probe.push(0);
+            let byte = (0..=255)
+                .find(|&c| {
+                    probe[alignment + block_size - 1] = c;
+                    &self.oracle.encrypt(&probe)[start..start + block_size] == target
+                })
+                .ok_or(EcbAttackError::NoMatchingByte { offset: recovered })?;
+            known.push(byte);
+        }
+        Ok(known.split_off(block_size - 1))
+    }
+}
+
+/// Forges an ECB encrypted `key=value&...` profile whose final value is
+/// `replacement` instead of `original`, the value the oracle normally ends with.
+///
+/// Our input is padded so `original` starts a block and a block holding just the
+/// padded `replacement` is encrypted separately; swapping it in for the tail gives
+/// a ciphertext that decrypts to the forged profile with valid padding.
+pub fn ecb_cut_and_paste(
+    oracle: &impl Oracle,
+    original: &[u8],
+    replacement: &[u8],
+) -> Result<Vec<u8>, EcbAttackError> {
+    let decryptor = EcbDecryptor::new(oracle)?;
+    let block_size = decryptor.block_size();
+    let prefix_length = decryptor.prefix_length();
+    if replacement.len() >= block_size {
+        return Err(EcbAttackError::ReplacementTooLong);
     }
+    let kept_suffix = decryptor
+        .suffix_length()
+        .checked_sub(original.len())
+        .ok_or(EcbAttackError::ReplacementTooLong)?;
+
+    let alignment = (block_size - prefix_length % block_size) % block_size;
+    let mut input = vec![b'A'; alignment];
+    input.extend(pkcs7::pad_to_blocksize(replacement.to_vec(), block_size));
+    let start = prefix_length + alignment;
+    let replacement_block = oracle.encrypt(&input)[start..start + block_size].to_vec();
+
+    let filler = (block_size - (prefix_length + kept_suffix) % block_size) % block_size;
+    let mut forged = oracle.encrypt(&vec![b'A'; filler]);
+    forged.truncate(prefix_length + filler + kept_suffix);
+    forged.extend(replacement_block);
+    Ok(forged)
+}
 
-    let mut output = vec![];
-    let mut offset = skip_length;
-    while offset < encrypted_secret.len() + 16 {
-        for i in (0..block_size).rev() {
-            // don't try to decrypt padding
-            let mut attack_prefix: Vec<u8> = "".as_bytes().to_vec();
-            attack_prefix.extend_from_slice(constant_prefix.clone().as_bytes());
-            attack_prefix.extend_from_slice("A".repeat(i).as_bytes());
-            let encrypted = oracle.encrypt(&attack_prefix);
-            attack_prefix.extend_from_slice(&output[..]);
-            attack_prefix.push(0);
-            let char = (0u8..=255).find(|c| {
-                let len = attack_prefix.len();
-                attack_prefix[len - 1] = c.clone();
-                let cipher = oracle.encrypt(&attack_prefix);
-                cipher[offset..offset + block_size] == encrypted[offset..offset + block_size]
-            });
+/// How many times to resubmit a query hoping a random prefix leaves it aligned.
+const MAX_ALIGNMENT_ATTEMPTS: usize = 1000;
 
-            if let Some(c) = char {
-                output.push(c);
-            } else {
-                return output;
+/// Wraps an ECB oracle that adds a random-length prefix, only returning
+/// ciphertext from calls where our input landed on a block boundary.
+///
+/// Each query is preceded by two different marker blocks; finding both of their
+/// encryptions side by side means everything after them is aligned.
+struct AlignedEcbOracle<'a, O: Oracle> {
+    oracle: &'a O,
+    block_size: usize,
+    markers: Vec<u8>,
+    encrypted_markers: Vec<u8>,
+}
+
+impl<'a, O: Oracle> AlignedEcbOracle<'a, O> {
+    fn new(oracle: &'a O) -> Result<Self, EcbAttackError> {
+        // every ciphertext is a whole number of blocks, whatever the prefix
+        let block_size = (0..32)
+            .map(|i| oracle.encrypt(&vec![b'A'; i]).len())
+            .fold(0, gcd);
+        if block_size < 2 {
+            return Err(EcbAttackError::UnknownBlockSize);
+        }
+
+        let mut markers = vec![];
+        let mut encrypted_markers = vec![];
+        for marker in [0xfe, 0xff] {
+            // three blocks of the same byte always contain two aligned copies
+            let ciphertext = oracle.encrypt(&vec![marker; block_size * 3]);
+            let i = first_repeated_block(&ciphertext, block_size).ok_or(EcbAttackError::NotEcb)?;
+            markers.extend(vec![marker; block_size]);
+            encrypted_markers.extend_from_slice(&ciphertext[i * block_size..(i + 1) * block_size]);
+        }
+
+        Ok(AlignedEcbOracle {
+            oracle,
+            block_size,
+            markers,
+            encrypted_markers,
+        })
+    }
+
+    /// The encryption of `input` and everything after it, from a call where
+    /// `input` started on a block boundary.
+    fn encrypt(&self, input: &[u8]) -> Result<Vec<u8>, EcbAttackError> {
+        let query = [&self.markers[..], input].concat();
+        for _ in 0..MAX_ALIGNMENT_ATTEMPTS {
+            let ciphertext = self.oracle.encrypt(&query);
+            let found = ciphertext
+                .windows(self.markers.len())
+                .step_by(self.block_size)
+                .position(|blocks| blocks == &self.encrypted_markers[..]);
+            if let Some(i) = found {
+                return Ok(ciphertext[i * self.block_size + self.markers.len()..].to_vec());
             }
         }
-        offset += block_size;
+        Err(EcbAttackError::MarkerNotFound)
     }
-    output
 }
 
-fn single_block_padding_attack(block: &[u8], oracle: &Challenge17) -> Vec<u8> {
-    let block_size = 16;
-    let mut zeroing_iv: Vec<u8> = vec![0; block_size];
-    for padding in 1..=block_size {
-        let mut found = false;
-        let mut padding_iv = zeroing_iv
-            .clone()
-            .into_iter()
-            .map(|i| i ^ padding as u8)
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Byte-at-a-time ECB decryption against an oracle that prepends a random-length
+/// prefix to every call.
+///
+/// Each byte is found with a single aligned query: a dictionary of all 256
+/// candidate blocks, followed by filler that pushes the unknown byte to the end
+/// of a block of the secret.
+pub fn attack_ecb_random_prefix(oracle: &impl Oracle) -> Result<Vec<u8>, EcbAttackError> {
+    let aligned = AlignedEcbOracle::new(oracle)?;
+    let block_size = aligned.block_size;
+
+    // the secret ends where adding filler first costs a whole block of padding
+    let empty_length = aligned.encrypt(&[])?.len();
+    let mut secret_length = None;
+    for i in 1..=block_size {
+        if aligned.encrypt(&vec![b'A'; i])?.len() != empty_length {
+            secret_length = Some(empty_length - i);
+            break;
+        }
+    }
+    let secret_length = secret_length.ok_or(EcbAttackError::UnknownBlockSize)?;
+
+    let mut known = vec![b'A'; block_size - 1];
+    while known.len() - (block_size - 1) < secret_length {
+        let recovered = known.len() - (block_size - 1);
+        let window = &known[known.len() - (block_size - 1)..];
+
+        let mut query = (0..=255u8)
+            .flat_map(|c| window.iter().copied().chain(std::iter::once(c)))
             .collect::<Vec<u8>>();
-        for j in 0..=255 {
-            let i = block_size - padding;
-            padding_iv[i] = j;
-            if oracle.is_valid_padding(&padding_iv[..], block) {
-                // change the penultimate byte to make the padding valid
-                let mut test = padding_iv.clone();
-                if i > 0 {
-                    test[i - 1] = test[i - 1] ^ 1;
-                }
-                if oracle.is_valid_padding(&test, block) {
-                    found = true;
-                    zeroing_iv[i] = padding_iv[i] ^ padding as u8;
-                    break;
-                }
+        let dictionary_length = query.len();
+        query.extend(vec![b'A'; block_size - 1 - recovered % block_size]);
+
+        let ciphertext = aligned.encrypt(&query)?;
+        let target = dictionary_length
+            + (block_size - 1 - recovered % block_size + recovered) / block_size * block_size;
+        let target = &ciphertext[target..target + block_size];
+        let byte = ciphertext[..dictionary_length]
+            .chunks(block_size)
+            .position(|block| block == target)
+            .ok_or(EcbAttackError::NoMatchingByte { offset: recovered })?;
+        known.push(byte as u8);
+    }
+    Ok(known.split_off(block_size - 1))
+}
+
+/// Decrypts the secret suffix of an ECB oracle, see [`EcbDecryptor`].
+pub fn attack_ecb(oracle: &impl Oracle) -> Result<Vec<u8>, EcbAttackError> {
+    EcbDecryptor::new(oracle)?.decrypt()
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PaddingOracleError {
+    /// The IV is not one block or the ciphertext is not a whole number of blocks.
+    InvalidLength,
+    /// No guess for this byte of this block was accepted by the oracle.
+    NoValidPadding { block: usize, byte: usize },
+    /// The recovered plaintext does not end in valid PKCS#7 padding.
+    InvalidPadding,
+}
+
+impl PaddingOracleError {
+    /// Single block attacks don't know where their block sits in the message.
+    fn in_block(self, block: usize) -> Self {
+        match self {
+            PaddingOracleError::NoValidPadding { byte, .. } => {
+                PaddingOracleError::NoValidPadding { block, byte }
             }
+            e => e,
         }
-        if !found {
-            panic!("Unable to find valid padding");
+    }
+}
+
+/// An IV has to be a single block and a ciphertext a whole, non-zero number of
+/// blocks.
+fn check_cbc_lengths(
+    iv: &[u8],
+    ciphertext: &[u8],
+    block_size: usize,
+) -> Result<(), PaddingOracleError> {
+    if iv.len() != block_size
+        || ciphertext.is_empty()
+        || !ciphertext.len().is_multiple_of(block_size)
+    {
+        return Err(PaddingOracleError::InvalidLength);
+    }
+    Ok(())
+}
+
+/// Finds byte `block.len() - padding` of the block cipher decryption of `block`,
+/// given the bytes after it in `intermediate`, by trying each candidate value in
+/// turn. Returns the value, if any, and the number of oracle queries spent.
+fn find_intermediate_byte(
+    block: &[u8],
+    intermediate: &[u8],
+    padding: usize,
+    candidates: impl IntoIterator<Item = u8>,
+    oracle: &impl PaddingOracle,
+) -> (Option<u8>, usize) {
+    let i = block.len() - padding;
+    let mut padding_iv = intermediate
+        .iter()
+        .map(|byte| byte ^ padding as u8)
+        .collect::<Vec<u8>>();
+    let mut queries = 0;
+    for candidate in candidates {
+        padding_iv[i] = candidate ^ padding as u8;
+        queries += 1;
+        if !oracle.is_valid_padding(&padding_iv, block) {
+            continue;
+        }
+        // only a single byte of padding can be mistaken for a longer run,
+        // so that is the only case that needs a second query
+        if padding == 1 && i > 0 {
+            let mut test = padding_iv.clone();
+            test[i - 1] ^= 1;
+            queries += 1;
+            if !oracle.is_valid_padding(&test, block) {
+                continue;
+            }
         }
+        return (Some(candidate), queries);
     }
-    zeroing_iv
+    (None, queries)
 }
 
-pub fn oracle_padding_attack(iv: &[u8], cipher: &[u8], oracle: &Challenge17) -> Vec<u8> {
-    let blocks = cipher.chunks(16).collect::<Vec<_>>();
-    
+/// Recovers the block cipher decryption of `block`, i.e. the plaintext before
+/// the CBC xor with the previous ciphertext block.
+fn single_block_padding_attack(
+    block: &[u8],
+    oracle: &impl PaddingOracle,
+) -> Result<Vec<u8>, PaddingOracleError> {
+    let block_size = block.len();
+    let mut intermediate = vec![0; block_size];
+    for padding in 1..=block_size {
+        let i = block_size - padding;
+        let (found, _) = find_intermediate_byte(block, &intermediate, padding, 0..=255, oracle);
+        intermediate[i] = found.ok_or(PaddingOracleError::NoValidPadding { block: 0, byte: i })?;
+    }
+    Ok(intermediate)
+}
+
+/// Decrypts `ciphertext` one byte at a time using only a padding oracle. The
+/// result still carries its PKCS#7 padding.
+pub fn oracle_padding_attack(
+    iv: &[u8],
+    ciphertext: &[u8],
+    oracle: &impl PaddingOracle,
+) -> Result<Vec<u8>, PaddingOracleError> {
+    let block_size = oracle.block_size();
+    check_cbc_lengths(iv, ciphertext, block_size)?;
+
     let mut result = vec![];
     let mut iv = iv.to_vec();
 
-    for block in blocks {
-        let mut decrypted = single_block_padding_attack(block, oracle);
+    for (index, block) in ciphertext.chunks(block_size).enumerate() {
+        let mut decrypted =
+            single_block_padding_attack(block, oracle).map_err(|e| e.in_block(index))?;
         decrypted.xor(&iv);
-        let plaintext = decrypted.clone();
         iv = block.to_vec();
-        result.extend_from_slice(&plaintext);
+        result.extend_from_slice(&decrypted);
+    }
+    Ok(result)
+}
+
+/// Like [`oracle_padding_attack`], but strips the recovered padding.
+pub fn oracle_padding_attack_stripped(
+    iv: &[u8],
+    ciphertext: &[u8],
+    oracle: &impl PaddingOracle,
+) -> Result<Vec<u8>, PaddingOracleError> {
+    let padded = oracle_padding_attack(iv, ciphertext, oracle)?;
+    strip_padding(padded).map_err(|_| PaddingOracleError::InvalidPadding)
+}
+
+/// Oracle queries spent by [`parallel_padding_oracle_attack`].
+#[derive(Debug, Default, PartialEq)]
+pub struct PaddingOracleStats {
+    /// Queries for each byte, indexed by block and then byte position.
+    pub queries: Vec<Vec<usize>>,
+}
+
+impl PaddingOracleStats {
+    pub fn block_queries(&self, block: usize) -> usize {
+        self.queries[block].iter().sum()
+    }
+
+    pub fn total_queries(&self) -> usize {
+        self.queries.iter().flatten().sum()
+    }
+}
+
+/// How likely `byte` is to appear in the plaintext: lowercase English first,
+/// then capitals, the rest of printable ASCII and finally everything else.
+fn plaintext_likelihood(byte: u8, padding_block: bool, block_size: usize) -> u32 {
+    match byte {
+        1..=255 if padding_block && byte as usize <= block_size => 200,
+        b' ' | b'a'..=b'z' => 100 + score_character(&(byte as char)),
+        b'A'..=b'Z' => 50 + score_character(&(byte.to_ascii_lowercase() as char)),
+        b'\n' | 0x20..=0x7e => 40,
+        _ => 0,
+    }
+}
+
+/// Every byte value, most likely plaintext first.
+fn plaintext_guess_order(padding_block: bool, block_size: usize) -> Vec<u8> {
+    let mut order = (0..=255).collect::<Vec<u8>>();
+    order.sort_by_key(|&byte| {
+        std::cmp::Reverse(plaintext_likelihood(byte, padding_block, block_size))
+    });
+    order
+}
+
+/// Decrypts one block, guessing plaintext bytes in likelihood order. Returns the
+/// plaintext and the number of queries spent on each byte.
+fn ordered_block_padding_attack(
+    block: &[u8],
+    previous: &[u8],
+    padding_block: bool,
+    oracle: &impl PaddingOracle,
+) -> Result<(Vec<u8>, Vec<usize>), PaddingOracleError> {
+    let block_size = block.len();
+    let order = plaintext_guess_order(padding_block, block_size);
+    let mut intermediate = vec![0; block_size];
+    let mut queries = vec![0; block_size];
+
+    for padding in 1..=block_size {
+        let i = block_size - padding;
+        // inside the padding of the final block every byte repeats the last one
+        let pad_value = intermediate[block_size - 1] ^ previous[block_size - 1];
+        let hint =
+            (padding_block && padding > 1 && padding <= pad_value as usize).then_some(pad_value);
+
+        let guesses = hint
+            .into_iter()
+            .chain(order.iter().copied())
+            .map(|guess| guess ^ previous[i]);
+        let (found, spent) = find_intermediate_byte(block, &intermediate, padding, guesses, oracle);
+        queries[i] = spent;
+        intermediate[i] = found.ok_or(PaddingOracleError::NoValidPadding { block: 0, byte: i })?;
+    }
+    intermediate.xor(&previous.to_vec());
+    Ok((intermediate, queries))
+}
+
+/// A padding oracle attack that splits the blocks over all available threads
+/// and guesses likely plaintext first. Returns the padded plaintext along with
+/// the number of oracle queries it took.
+pub fn parallel_padding_oracle_attack<O: PaddingOracle + Sync>(
+    iv: &[u8],
+    ciphertext: &[u8],
+    oracle: &O,
+) -> Result<(Vec<u8>, PaddingOracleStats), PaddingOracleError> {
+    let block_size = oracle.block_size();
+    check_cbc_lengths(iv, ciphertext, block_size)?;
+
+    let blocks = ciphertext.chunks(block_size).collect::<Vec<_>>();
+    let previous = std::iter::once(iv)
+        .chain(blocks.iter().copied())
+        .collect::<Vec<_>>();
+    let last = blocks.len() - 1;
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = blocks.len().div_ceil(threads);
+
+    let results = std::thread::scope(|scope| {
+        let handles = blocks
+            .chunks(chunk_size)
+            .enumerate()
+            .map(|(chunk, blocks)| {
+                let previous = &previous;
+                scope.spawn(move || {
+                    blocks
+                        .iter()
+                        .enumerate()
+                        .map(|(i, block)| {
+                            let index = chunk * chunk_size + i;
+                            ordered_block_padding_attack(block, previous[index], index == last, oracle)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("Padding oracle thread panicked"))
+            .collect::<Vec<_>>()
+    });
+
+    let mut plaintext = vec![];
+    let mut stats = PaddingOracleStats::default();
+    for (index, result) in results.into_iter().enumerate() {
+        let (block, queries) = result.map_err(|e| e.in_block(index))?;
+        plaintext.extend_from_slice(&block);
+        stats.queries.push(queries);
+    }
+    Ok((plaintext, stats))
+}
+
+/// Tells slow (validly padded) responses from fast ones by timing a
+/// [`TimingOracle`].
+pub struct TimingClassifier {
+    threshold: Duration,
+    samples: usize,
+}
+
+impl TimingClassifier {
+    /// Picks a threshold between the timing of a known-good message (the final
+    /// block of a real ciphertext, behind its real predecessor) and of the same
+    /// block behind random IVs, which are almost always badly padded.
+    pub fn calibrate(
+        oracle: &impl TimingOracle,
+        iv: &[u8],
+        ciphertext: &[u8],
+        samples: usize,
+    ) -> Result<Self, PaddingOracleError> {
+        let block_size = oracle.block_size();
+        check_cbc_lengths(iv, ciphertext, block_size)?;
+        let blocks = std::iter::once(iv)
+            .chain(ciphertext.chunks(block_size))
+            .collect::<Vec<_>>();
+        let (previous, last) = (blocks[blocks.len() - 2], blocks[blocks.len() - 1]);
+
+        let mut classifier = TimingClassifier {
+            threshold: Duration::ZERO,
+            samples,
+        };
+        let rounds = 15;
+        let mut valid = (0..rounds)
+            .map(|_| classifier.measure(oracle, previous, last))
+            .collect::<Vec<_>>();
+        let mut invalid = (0..rounds)
+            .map(|_| classifier.measure(oracle, &random_key(block_size), last))
+            .collect::<Vec<_>>();
+        valid.sort();
+        invalid.sort();
+        classifier.threshold = (valid[rounds / 2] + invalid[rounds / 2]) / 2;
+        Ok(classifier)
+    }
+
+    /// The quickest of several responses, since noise only ever adds time.
+    fn measure(&self, oracle: &impl TimingOracle, iv: &[u8], ciphertext: &[u8]) -> Duration {
+        (0..self.samples)
+            .map(|_| oracle.response_time(iv, ciphertext))
+            .min()
+            .unwrap_or_default()
+    }
+
+    pub fn threshold(&self) -> Duration {
+        self.threshold
+    }
+
+    pub fn is_slow(&self, oracle: &impl TimingOracle, iv: &[u8], ciphertext: &[u8]) -> bool {
+        self.measure(oracle, iv, ciphertext) > self.threshold
+    }
+}
+
+/// Turns a [`TimingOracle`] back into a [`PaddingOracle`] by classifying its
+/// response times.
+pub struct TimingPaddingOracle<'a, O: TimingOracle> {
+    oracle: &'a O,
+    classifier: TimingClassifier,
+}
+
+impl<'a, O: TimingOracle> TimingPaddingOracle<'a, O> {
+    pub fn new(oracle: &'a O, classifier: TimingClassifier) -> Self {
+        Self { oracle, classifier }
+    }
+}
+
+impl<O: TimingOracle> PaddingOracle for TimingPaddingOracle<'_, O> {
+    fn block_size(&self) -> usize {
+        self.oracle.block_size()
+    }
+
+    fn is_valid_padding(&self, iv: &[u8], ciphertext: &[u8]) -> bool {
+        self.classifier.is_slow(self.oracle, iv, ciphertext)
+    }
+}
+
+/// Decrypts `ciphertext` through an oracle that only leaks response times.
+pub fn timing_padding_oracle_attack(
+    iv: &[u8],
+    ciphertext: &[u8],
+    oracle: &impl TimingOracle,
+) -> Result<Vec<u8>, PaddingOracleError> {
+    let classifier = TimingClassifier::calibrate(oracle, iv, ciphertext, 3)?;
+    info!("Timing threshold: {:?}", classifier.threshold());
+    oracle_padding_attack_stripped(iv, ciphertext, &TimingPaddingOracle::new(oracle, classifier))
+}
+
+/// Forges an IV and ciphertext that decrypt to `plaintext` using only a padding
+/// oracle ("CBC-R").
+///
+/// Works backwards from an arbitrary final block: once the oracle reveals what a
+/// block decrypts to, the preceding block is chosen to xor that into the wanted
+/// plaintext.
+pub fn padding_oracle_encrypt(
+    plaintext: &[u8],
+    oracle: &impl PaddingOracle,
+) -> Result<(Vec<u8>, Vec<u8>), PaddingOracleError> {
+    let block_size = oracle.block_size();
+    let padded = pkcs7::pad_to_blocksize(plaintext.to_vec(), block_size);
+    let block_count = padded.len() / block_size;
+
+    let mut blocks = vec![random_key(block_size)];
+    for (index, target) in padded.chunks(block_size).enumerate().rev() {
+        let mut previous = single_block_padding_attack(blocks.last().unwrap(), oracle)
+            .map_err(|e| e.in_block(index))?;
+        previous.xor(&target.to_vec());
+        blocks.push(previous);
+    }
+    blocks.reverse();
+
+    let iv = blocks[0].clone();
+    let ciphertext = blocks[1..=block_count].concat();
+    Ok((iv, ciphertext))
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BitflipError {
+    /// Changing the input never changed the ciphertext.
+    InputNotReflected,
+    /// In a block mode only a single block can be rewritten cleanly.
+    TargetTooLong,
+}
+
+fn first_difference(a: &[u8], b: &[u8]) -> Option<usize> {
+    a.iter().zip(b.iter()).position(|(x, y)| x != y)
+}
+
+/// How many bytes the ciphertext grows by at a time: the block size for block
+/// modes and 1 for stream modes.
+fn ciphertext_granularity(oracle: &impl Oracle) -> Option<usize> {
+    let base = oracle.encrypt(&[]).len();
+    (1..=256).find_map(|i| {
+        let len = oracle.encrypt(&vec![b'A'; i]).len();
+        (len != base).then(|| len - base)
+    })
+}
+
+fn flip(ciphertext: &mut [u8], known: &[u8], target: &[u8]) {
+    for (i, (k, t)) in known.iter().zip(target.iter()).enumerate() {
+        ciphertext[i] ^= k ^ t;
+    }
+}
+
+/// Forges a ciphertext whose plaintext contains `target`, against an oracle that
+/// encrypts attacker input between an unknown prefix and suffix with CTR or CBC.
+///
+/// The input is only ever filled with `A`s, so quoting of special characters by
+/// the oracle never gets in the way.
+pub fn bitflip_inject(oracle: &impl Oracle, target: &[u8]) -> Result<Vec<u8>, BitflipError> {
+    match ciphertext_granularity(oracle).ok_or(BitflipError::InputNotReflected)? {
+        1 => stream_bitflip_inject(oracle, target),
+        block_size => cbc_bitflip(oracle, target, block_size),
+    }
+}
+
+/// Forges a CBC ciphertext whose plaintext contains `target`, which must fit in a
+/// single block, by rewriting the ciphertext of the block before it.
+pub fn cbc_bitflip_inject(oracle: &impl Oracle, target: &[u8]) -> Result<Vec<u8>, BitflipError> {
+    let block_size = ciphertext_granularity(oracle).ok_or(BitflipError::InputNotReflected)?;
+    cbc_bitflip(oracle, target, block_size)
+}
+
+/// The length of whatever a CBC oracle puts in front of the input.
+///
+/// The first ciphertext block that changes with the input is the one the input
+/// starts in; pushing a changed byte further along until it spills into the next
+/// block gives the exact offset within that block.
+pub fn get_cbc_prefix_length(block_size: usize, oracle: &impl Oracle) -> Option<usize> {
+    let changed_block = |a: &[u8], b: &[u8]| {
+        first_difference(&oracle.encrypt(a), &oracle.encrypt(b)).map(|i| i / block_size)
+    };
+    let start_block = changed_block(b"A", b"B")?;
+    let spill = (1..=block_size).find(|k| {
+        let a = vec![b'A'; k + 1];
+        let mut b = a.clone();
+        b[*k] = b'B';
+        changed_block(&a, &b) > Some(start_block)
+    })?;
+    Some((start_block + 1) * block_size - spill)
+}
+
+fn stream_bitflip_inject(oracle: &impl Oracle, target: &[u8]) -> Result<Vec<u8>, BitflipError> {
+    // with a stream cipher the first changed byte is exactly where the input starts
+    let prefix_length = first_difference(&oracle.encrypt(b"A"), &oracle.encrypt(b"B"))
+        .ok_or(BitflipError::InputNotReflected)?;
+    let filler = vec![b'A'; target.len()];
+    let mut ciphertext = oracle.encrypt(&filler);
+    flip(&mut ciphertext[prefix_length..], &filler, target);
+    Ok(ciphertext)
+}
+
+fn cbc_bitflip(
+    oracle: &impl Oracle,
+    target: &[u8],
+    block_size: usize,
+) -> Result<Vec<u8>, BitflipError> {
+    if target.len() > block_size {
+        return Err(BitflipError::TargetTooLong);
+    }
+    let prefix_length =
+        get_cbc_prefix_length(block_size, oracle).ok_or(BitflipError::InputNotReflected)?;
+
+    // align to a block boundary, then a sacrificial block whose ciphertext is
+    // rewritten to control the decryption of the block after it
+    let alignment = (block_size - prefix_length % block_size) % block_size;
+    let filler = vec![b'A'; alignment + 2 * block_size];
+    let mut ciphertext = oracle.encrypt(&filler);
+    flip(&mut ciphertext[prefix_length + alignment..], &filler, target);
+    Ok(ciphertext)
+}
+
+/// Recovers the key from a CBC oracle that uses the key as its IV.
+///
+/// Decrypting C1 || 0 || C1 gives P1 = D(C1) ^ key and P3 = D(C1) ^ 0, so the
+/// plaintext leaked in the rejection hands over key = P1 ^ P3.
+pub fn recover_key_as_iv(oracle: &impl KeyAsIvOracle) -> Option<Vec<u8>> {
+    let block_size = oracle.block_size();
+    let ciphertext = oracle.encrypt(b"");
+    if ciphertext.len() < 3 * block_size {
+        return None;
+    }
+    let first = &ciphertext[..block_size];
+
+    let mut forged = first.to_vec();
+    forged.extend_from_slice(&vec![0; block_size]);
+    forged.extend_from_slice(first);
+    // keep the original tail so the padding is still valid
+    forged.extend_from_slice(&ciphertext[3 * block_size..]);
+
+    let plaintext = oracle.rejected_plaintext(&forged)?;
+    let mut key = plaintext[..block_size].to_vec();
+    key.xor(&plaintext[2 * block_size..3 * block_size].to_vec());
+    Some(key)
+}
+
+/// Recovers the plaintext behind a CTR ciphertext from a random-access edit oracle.
+pub fn break_ctr_edit(oracle: &impl EditOracle, ciphertext: &[u8]) -> Vec<u8> {
+    // "editing" the ciphertext into itself XORs it with the keystream once more
+    oracle.edit(ciphertext, 0, ciphertext)
+}
+
+/// Keystream recovered from many CTR ciphertexts that share a nonce.
+pub struct FixedNonceCtrBreak {
+    ciphertexts: Vec<Vec<u8>>,
+    keystream: Vec<u8>,
+}
+
+impl FixedNonceCtrBreak {
+    pub fn keystream(&self) -> &[u8] {
+        &self.keystream
+    }
+
+    /// Every ciphertext decrypted with the current keystream.
+    pub fn plaintexts(&self) -> Vec<Vec<u8>> {
+        self.ciphertexts
+            .iter()
+            .map(|ciphertext| self.plaintext(ciphertext))
+            .collect()
+    }
+
+    fn plaintext(&self, ciphertext: &[u8]) -> Vec<u8> {
+        ciphertext
+            .iter()
+            .zip(&self.keystream)
+            .map(|(c, k)| c ^ k)
+            .collect()
+    }
+
+    /// Patches the keystream so that ciphertext `line` decrypts to `known` at
+    /// `offset`, fixing that column in every other line too. Anything past the
+    /// end of that ciphertext is ignored.
+    ///
+    /// Returns how many keystream bytes were patched, or `None` if there is no
+    /// ciphertext `line`.
+    pub fn apply_hint(&mut self, line: usize, offset: usize, known: &[u8]) -> Option<usize> {
+        let ciphertext = self.ciphertexts.get(line)?;
+        let mut patched = 0;
+        for (i, byte) in known.iter().enumerate() {
+            if let Some(c) = ciphertext.get(offset + i) {
+                self.keystream[offset + i] = c ^ byte;
+                patched += 1;
+            }
+        }
+        Some(patched)
+    }
+}
+
+/// Statistically recovers the keystream shared by CTR ciphertexts encrypted
+/// under a fixed nonce.
+///
+/// Truncated to their common length the ciphertexts are one repeating-key XOR
+/// ciphertext with a key as long as that length. The columns past the shortest
+/// line are broken the same way using whichever lines are long enough, and are
+/// the least reliable; [`FixedNonceCtrBreak::apply_hint`] can patch them.
+pub fn break_fixed_nonce_ctr(ciphertexts: &[Vec<u8>], scorer: &impl Scorer) -> FixedNonceCtrBreak {
+    let common = ciphertexts.iter().map(Vec::len).min().unwrap_or(0);
+    let longest = ciphertexts.iter().map(Vec::len).max().unwrap_or(0);
+
+    let truncated = ciphertexts
+        .iter()
+        .flat_map(|ciphertext| &ciphertext[..common])
+        .copied()
+        .collect::<Vec<_>>();
+    let mut keystream = if common > 0 {
+        transpose(&truncated, common)
+            .into_iter()
+            .map(|column| attack_single_character_xor(column, scorer).key)
+            .collect()
+    } else {
+        vec![]
+    };
+
+    for i in common..longest {
+        let column = ciphertexts
+            .iter()
+            .filter_map(|ciphertext| ciphertext.get(i))
+            .copied()
+            .collect::<Vec<_>>();
+        keystream.push(attack_single_character_xor(column, scorer).key);
+    }
+
+    FixedNonceCtrBreak {
+        ciphertexts: ciphertexts.to_vec(),
+        keystream,
+    }
+}
+
+/// A crib placed in one ciphertext that makes another decrypt to something
+/// plausible.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CribMatch {
+    /// The ciphertext the crib is assumed to be in.
+    pub line: usize,
+    /// The ciphertext it was xored against.
+    pub other: usize,
+    pub offset: usize,
+    pub crib: Vec<u8>,
+    /// What `other` decrypts to at `offset` if the crib is right.
+    pub fragment: Vec<u8>,
+    /// Per-byte score of `fragment` under [`score_character`], ignoring case.
+    pub score: f64,
+}
+
+/// Crib dragging over ciphertexts that were XORed with the same keystream.
+///
+/// The XOR of two ciphertexts is the XOR of their plaintexts, so guessing a word
+/// in one reveals the other at the same position. Guesses that hold up can be
+/// locked in as keystream, which then decrypts every ciphertext at once.
+pub struct CribDragger {
+    ciphertexts: Vec<Vec<u8>>,
+    keystream: Vec<Option<u8>>,
+    min_score: f64,
+}
+
+impl CribDragger {
+    pub fn new(ciphertexts: Vec<Vec<u8>>) -> Self {
+        let longest = ciphertexts.iter().map(Vec::len).max().unwrap_or(0);
+        CribDragger {
+            ciphertexts,
+            keystream: vec![None; longest],
+            min_score: 2.,
+        }
+    }
+
+    /// Fragments scoring less than this per byte are not reported.
+    pub fn with_min_score(mut self, min_score: f64) -> Self {
+        self.min_score = min_score;
+        self
+    }
+
+    pub fn keystream(&self) -> &[Option<u8>] {
+        &self.keystream
+    }
+
+    /// Slides `crib` across every ordered pair of ciphertexts and returns the
+    /// placements where the other plaintext comes out printable and
+    /// English-like, best first. Placements that contradict locked keystream
+    /// are skipped.
+    pub fn drag(&self, crib: &[u8]) -> Vec<CribMatch> {
+        let scorer = CaseInsensitive(CharacterTable);
+        let mut matches = vec![];
+        for (line, ciphertext) in self.ciphertexts.iter().enumerate() {
+            for offset in 0..(ciphertext.len() + 1).saturating_sub(crib.len()) {
+                let implied_keystream = crib
+                    .iter()
+                    .zip(&ciphertext[offset..])
+                    .map(|(p, c)| p ^ c)
+                    .collect::<Vec<_>>();
+                let consistent = implied_keystream
+                    .iter()
+                    .zip(&self.keystream[offset..])
+                    .all(|(k, locked)| locked.is_none_or(|locked| locked == *k));
+                if !consistent {
+                    continue;
+                }
+
+                for (other, other_ciphertext) in self.ciphertexts.iter().enumerate() {
+                    if other == line || other_ciphertext.len() < offset + crib.len() {
+                        continue;
+                    }
+                    let fragment = other_ciphertext[offset..offset + crib.len()]
+                        .iter()
+                        .zip(&implied_keystream)
+                        .map(|(c, k)| c ^ k)
+                        .collect::<Vec<_>>();
+                    if !fragment.iter().all(|&b| matches!(b, 0x20..=0x7e)) {
+                        continue;
+                    }
+                    let score = scorer.score(&fragment) / crib.len() as f64;
+                    if score >= self.min_score {
+                        matches.push(CribMatch {
+                            line,
+                            other,
+                            offset,
+                            crib: crib.to_vec(),
+                            fragment,
+                            score,
+                        });
+                    }
+                }
+            }
+        }
+        matches.sort_by(|a, b| b.score.total_cmp(&a.score));
+        matches
+    }
+
+    /// Locks in the keystream that makes ciphertext `line` read `known` at
+    /// `offset`, replacing anything locked there before. Anything past the end
+    /// of that ciphertext is ignored.
+    ///
+    /// Returns how many keystream bytes were locked, or `None` if there is no
+    /// ciphertext `line`.
+    pub fn lock(&mut self, line: usize, offset: usize, known: &[u8]) -> Option<usize> {
+        let ciphertext = self.ciphertexts.get(line)?;
+        let mut locked = 0;
+        for (i, byte) in known.iter().enumerate() {
+            if let Some(c) = ciphertext.get(offset + i) {
+                self.keystream[offset + i] = Some(c ^ byte);
+                locked += 1;
+            }
+        }
+        Some(locked)
+    }
+
+    pub fn lock_match(&mut self, found: &CribMatch) -> Option<usize> {
+        self.lock(found.line, found.offset, &found.crib)
+    }
+
+    /// Every ciphertext decrypted as far as the keystream is known.
+    pub fn plaintexts(&self) -> Vec<Vec<Option<u8>>> {
+        self.ciphertexts
+            .iter()
+            .map(|ciphertext| {
+                ciphertext
+                    .iter()
+                    .zip(&self.keystream)
+                    .map(|(c, k)| k.map(|k| c ^ k))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The plaintexts with `_` standing in for bytes that are still unknown.
+    pub fn render(&self) -> Vec<String> {
+        self.plaintexts()
+            .iter()
+            .map(|line| line.iter().map(|b| b.map_or('_', |b| b as char)).collect())
+            .collect()
     }
-    result
 }
 
 // given the first output from a seeded mt19937 rng, determine the seed
@@ -289,9 +1363,68 @@ pub fn brute_force_mt19377_seed(seed_space: (u32, u32), output: u32) -> Option<u
     None
 }
 
+/// A GCM ciphertext as seen on the wire.
+pub struct GcmMessage {
+    pub aad: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+    pub tag: [u8; 16],
+}
+
+/// The polynomial g(X) with g(H) = E(K, J0): the GHASH blocks as coefficients of
+/// descending powers of X, plus the tag as the constant term.
+pub fn gcm_tag_polynomial(message: &GcmMessage) -> Poly {
+    let mut blocks = vec![];
+    blocks.extend(message.aad.chunks(16).map(Gf128::from_bytes));
+    blocks.extend(message.ciphertext.chunks(16).map(Gf128::from_bytes));
+    let mut lengths = ((message.aad.len() as u64) * 8).to_be_bytes().to_vec();
+    lengths.extend_from_slice(&((message.ciphertext.len() as u64) * 8).to_be_bytes());
+    blocks.push(Gf128::from_bytes(&lengths));
+    blocks.push(Gf128::from_bytes(&message.tag));
+    blocks.reverse();
+    Poly::new(blocks)
+}
+
+/// Recovers the candidate GHASH keys from messages that reused a key and nonce.
+///
+/// Every pair shares the same mask E(K, J0), so H is a root of the difference of
+/// their tag polynomials; each extra message narrows the candidates further.
+pub fn gcm_nonce_reuse_hash_keys(messages: &[GcmMessage]) -> Vec<Gf128> {
+    assert!(messages.len() >= 2, "Need at least two messages under the same nonce");
+    let first = gcm_tag_polynomial(&messages[0]);
+    let difference = &first + &gcm_tag_polynomial(&messages[1]);
+    let mut candidates = roots(&difference);
+
+    for message in &messages[2..] {
+        let difference = &first + &gcm_tag_polynomial(message);
+        candidates.retain(|h| difference.evaluate(*h).is_zero());
+    }
+    candidates
+}
+
+/// Computes a valid tag for `aad` and `ciphertext` under the nonce of `known`,
+/// given the hash key H.
+pub fn gcm_forge_tag(h: Gf128, known: &GcmMessage, aad: &[u8], ciphertext: &[u8]) -> [u8; 16] {
+    let mask = Gf128::from_bytes(&known.tag) + ghash(h, &known.aad, &known.ciphertext);
+    (ghash(h, aad, ciphertext) + mask).to_bytes()
+}
+
 #[cfg(test)]
 mod tests {
     use crate::attacks::*;
+    use crate::block_cipher::{Aes192, BlockCipher};
+    use crate::gcm::Gcm;
+    use crate::cbc::{cbc_decrypt, cbc_encrypt};
+    use crate::challenge_16::Challenge16;
+    use crate::challenge_17::{Challenge17, TimedChallenge17};
+    use crate::challenge_26::Challenge26;
+    use crate::challenge_27::Challenge27;
+    use crate::cookie::ProfileManager;
+    use crate::ctr::CTROracle;
+    use crate::oracle::AdminOracle;
+    use crate::scoring::{ChiSquared, PrintablePenalty};
+    use crate::utils::{decrypt_aes_128_padded, random_key};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::oracle::StaticOracle;
 
     #[test]
     fn test_hamming_distance() {
@@ -300,20 +1433,553 @@ mod tests {
         assert_eq!(hamming_distance(a, b), 37);
     }
 
+    fn check_challenge_3(scorer: &impl Scorer) {
+        let ciphertext = Vec::<u8>::from_hex(
+            "1b37373331363f78151b7f2b783431333d78397828372d363c78373e783a393b3736",
+        );
+        let result = attack_single_character_xor(ciphertext, scorer);
+        assert_eq!(result.key, b'X');
+        assert_eq!(result.decoded, b"Cooking MC's like a pound of bacon");
+    }
+
+    #[test]
+    fn test_attack_single_character_xor_scorers() {
+        check_challenge_3(&CharacterTable);
+        check_challenge_3(&ChiSquared::english());
+        check_challenge_3(&ChiSquared::english().with_bigram_weight(0.5));
+        check_challenge_3(&PrintablePenalty::new(CaseInsensitive(CharacterTable), 20.));
+    }
+
+    #[test]
+    fn test_detect_single_character_xor_challenge_4() {
+        let candidates = detect_single_character_xor_in_file(
+            "data/4.txt",
+            LineEncoding::Hex,
+            3,
+            &ChiSquared::english(),
+        )
+        .unwrap();
+        assert_eq!(candidates.len(), 3);
+        assert_eq!(candidates[0].line, 171);
+        assert_eq!(candidates[0].key, 0x35);
+        assert_eq!(candidates[0].plaintext, b"Now that the party is jumping\n");
+        assert!(candidates[0].score > candidates[1].score);
+    }
+
+    #[test]
+    fn test_detect_single_character_xor_in_file_bad_line() {
+        let path = std::env::temp_dir().join(format!("cryptopals-bad-line-{}", std::process::id()));
+        for (encoding, bad) in [(LineEncoding::Hex, "0g"), (LineEncoding::Base64, "QQ=!")] {
+            std::fs::write(&path, format!("4142\n\n{}\n", bad)).unwrap();
+            let error = detect_single_character_xor_in_file(&path, encoding, 1, &CharacterTable)
+                .err()
+                .unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            assert!(error.to_string().contains("line 3"));
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_detect_single_character_xor_line_numbers() {
+        let mut secret = b"the quick brown fox jumps over the lazy dog".to_vec();
+        secret.xor(&b'k');
+        let ciphertexts = (0..50)
+            .map(|i| if i == 37 { secret.clone() } else { random_key(43) })
+            .chain(std::iter::once(vec![]));
+
+        let candidates = detect_single_character_xor(ciphertexts, 1, &ChiSquared::english());
+        assert_eq!(candidates[0].line, 38);
+        assert_eq!(candidates[0].key, b'k');
+    }
+
+    fn challenge_19_ciphertexts() -> (Vec<Vec<u8>>, Vec<Vec<u8>>) {
+        let oracle = CTROracle::new([0; 8]);
+        let plaintexts = std::fs::read_to_string("data/19.txt")
+            .unwrap()
+            .lines()
+            .map(Vec::<u8>::from_base64)
+            .collect::<Vec<_>>();
+        let ciphertexts = plaintexts.iter().map(|line| oracle.encrypt(line)).collect();
+        (plaintexts, ciphertexts)
+    }
+
+    #[test]
+    fn test_break_fixed_nonce_ctr() {
+        let (plaintexts, ciphertexts) = challenge_19_ciphertexts();
+        let mut broken = break_fixed_nonce_ctr(&ciphertexts, &ChiSquared::english());
+        let longest = ciphertexts.iter().map(Vec::len).max().unwrap();
+        assert_eq!(broken.keystream().len(), longest);
+
+        // the statistics can't always tell case apart, but the letters come out
+        let recovered = broken.plaintexts();
+        let matching = recovered
+            .iter()
+            .flatten()
+            .zip(plaintexts.iter().flatten())
+            .filter(|(a, b)| a.eq_ignore_ascii_case(b))
+            .count();
+        let total = plaintexts.iter().map(Vec::len).sum::<usize>();
+        assert!(matching * 10 > total * 8, "{} of {}", matching, total);
+
+        // the longest line pins down the whole keystream
+        let line = ciphertexts.iter().position(|c| c.len() == longest).unwrap();
+        assert_eq!(broken.apply_hint(line, 0, &plaintexts[line]), Some(longest));
+        assert_eq!(broken.plaintexts(), plaintexts);
+
+        assert_eq!(broken.apply_hint(line, longest - 2, b"extra"), Some(2));
+        assert_eq!(broken.apply_hint(ciphertexts.len(), 0, b"no such line"), None);
+    }
+
+    #[test]
+    fn test_crib_dragging() {
+        let (plaintexts, ciphertexts) = challenge_19_ciphertexts();
+        let (plaintexts, ciphertexts) = (&plaintexts[..6], ciphertexts[..6].to_vec());
+        let mut dragger = CribDragger::new(ciphertexts);
+
+        // line 4 reads "I have passed with a nod of the head"
+        let matches = dragger.drag(b" with ");
+        let found = matches
+            .iter()
+            .find(|m| m.line == 4 && m.offset == 13 && m.other == 0)
+            .expect("The true placement was not reported")
+            .clone();
+        assert_eq!(found.fragment, b"em at ");
+        assert!(matches.windows(2).all(|w| w[0].score >= w[1].score));
+
+        assert_eq!(dragger.lock_match(&found), Some(6));
+        let recovered = dragger.plaintexts();
+        for (line, plaintext) in plaintexts.iter().enumerate() {
+            assert_eq!(recovered[line][12], None);
+            let expected = plaintext[13..19].iter().map(|&b| Some(b)).collect::<Vec<_>>();
+            assert_eq!(recovered[line][13..19], expected);
+        }
+        assert_eq!(dragger.render()[4], "_____________ with _________________");
+
+        // placements that disagree with the locked columns are no longer offered
+        let relocked = dragger.drag(b" with ");
+        assert!(relocked.len() < matches.len());
+        for m in relocked {
+            for i in (m.offset..m.offset + 6).filter(|i| (13..19).contains(i)) {
+                assert_eq!(plaintexts[m.line][i], m.crib[i - m.offset]);
+            }
+        }
+
+        assert_eq!(dragger.lock(0, 0, b"I have met them at close of day"), Some(31));
+        assert_eq!(dragger.render()[1], "Coming with vivid faces");
+        assert_eq!(dragger.lock(6, 0, b"no such line"), None);
+    }
+
+    #[test]
+    fn test_keysize_edit_distance_uses_sixteen_chunks() {
+        // ten matching chunks then six that differ pairwise: all sixteen count,
+        // but nothing after them does
+        let mut ciphertext = [b"abcd".repeat(10), b"abcdzzzz".repeat(3)].concat();
+        let distance = keysize_edit_distance(&ciphertext, 4);
+        let expected = 3. * hamming_distance(b"abcd", b"zzzz") as f32 / 4. / 8.;
+        assert_eq!(distance, expected);
+        ciphertext.extend(random_key(64));
+        assert_eq!(keysize_edit_distance(&ciphertext, 4), distance);
+    }
+
+    #[test]
+    fn test_break_repeating_key_xor_challenge_6() {
+        let input = std::fs::read_to_string("data/6.txt").unwrap().replace('\n', "");
+        let ciphertext = Vec::<u8>::from_base64(&input);
+        let candidates = break_repeating_key_xor(&ciphertext, 2..=40, 5, &ChiSquared::english());
+
+        assert_eq!(candidates[0].key, b"Terminator X: Bring the noise");
+        assert!(candidates[0].plaintext.starts_with(b"I'm back and I'm ringin' the bell"));
+        assert!(candidates[0].confidence > 0.9);
+        assert!(candidates.windows(2).all(|w| w[0].confidence >= w[1].confidence));
+        assert_eq!(
+            attack_repeating_key_xor(&ciphertext, &ChiSquared::english()),
+            Some(candidates[0].key.clone())
+        );
+        assert_eq!(attack_repeating_key_xor(b"abc", &ChiSquared::english()), None);
+    }
+
+    #[test]
+    fn test_rank_keysizes_range() {
+        let plaintext = b"Burning 'em, if you ain't quick and nimble. I go crazy when I hear a cymbal \
+            and a high hat with a souped up tempo. I'm on a roll, it's time to go solo.";
+        let ciphertext = plaintext.as_slice().encrypt_repeating_key_xor(b"ICEBOX");
+        let ranked = rank_keysizes(&ciphertext, 3..=8);
+        assert!(ranked.iter().all(|keysize| (3..=8).contains(keysize)));
+        assert!(ranked[..3].contains(&6));
+
+        let candidates = break_repeating_key_xor(&ciphertext, 3..=8, 3, &ChiSquared::english());
+        assert_eq!(candidates[0].key, b"ICEBOX");
+        assert_eq!(candidates[0].plaintext, plaintext);
+    }
+
     #[test]
     fn test_get_prefix_length() {
         let oracle = StaticOracle::new().with_prefix("SUBMARINE SUBMARINE".as_bytes());
-        assert_eq!(get_prefix_length(16, &oracle), 19);
+        assert_eq!(get_prefix_length(16, &oracle), Some(19));
+    }
+
+    #[test]
+    fn test_get_prefix_length_ending_in_fill_byte() {
+        for prefix in [&b"YELLOW SUBMARINEA"[..], b"YELLOW SUBMARINEB", b"A", b"B", b"C"] {
+            let oracle = StaticOracle::new().with_prefix(prefix);
+            assert_eq!(get_prefix_length(16, &oracle), Some(prefix.len()));
+
+            let oracle = StaticOracle::<Toy64>::with_key(&random_key(8))
+                .with_prefix(prefix)
+                .with_suffix(b"attack at dawn");
+            let decryptor = EcbDecryptor::new(&oracle).unwrap();
+            assert_eq!(decryptor.prefix_length(), prefix.len());
+            assert_eq!(decryptor.decrypt(), Ok(b"attack at dawn".to_vec()));
+        }
+    }
+
+    #[test]
+    fn test_get_prefix_length_suffix_starting_with_fill_byte() {
+        for suffix in [&b"Attack at dawn"[..], b"Bttack at dawn", b"Cttack at dawn"] {
+            let oracle = StaticOracle::new()
+                .with_prefix(b"SUBMARINE SUBMARINE")
+                .with_suffix(suffix);
+            assert_eq!(get_prefix_length(16, &oracle), Some(19));
+
+            let oracle = StaticOracle::<Toy64>::with_key(&random_key(8))
+                .with_prefix(b"SUBMARINE")
+                .with_suffix(suffix);
+            let decryptor = EcbDecryptor::new(&oracle).unwrap();
+            assert_eq!(decryptor.prefix_length(), 9);
+            assert_eq!(decryptor.decrypt(), Ok(suffix.to_vec()));
+        }
+    }
+
+    #[test]
+    fn test_attack_ecb_aes_192() {
+        let oracle = StaticOracle::<Aes192>::with_key(&[3; 24])
+            .with_prefix(b"prefix")
+            .with_suffix(b"attack at dawn");
+        assert_eq!(attack_ecb(&oracle), Ok(b"attack at dawn".to_vec()));
+    }
+
+    #[test]
+    fn test_ecb_decryptor_8_byte_blocks() {
+        let secret = b"Rollin' in my 5.0\nWith my rag-top down";
+        for prefix_length in 0..20 {
+            let oracle = StaticOracle::<Toy64>::with_key(&random_key(8))
+                .with_prefix(&random_key(prefix_length))
+                .with_suffix(secret);
+            let decryptor = EcbDecryptor::new(&oracle).unwrap();
+            assert_eq!(decryptor.block_size(), 8);
+            assert_eq!(decryptor.prefix_length(), prefix_length);
+            assert_eq!(decryptor.suffix_length(), secret.len());
+            assert_eq!(decryptor.decrypt(), Ok(secret.to_vec()));
+        }
+    }
+
+    #[test]
+    fn test_attack_ecb_random_prefix() {
+        let secret = Vec::<u8>::from_base64("Um9sbGluJyBpbiBteSA1LjAKV2l0aCBteSByYWctdG9wIGRvd24gc28gbXkgaGFpciBjYW4gYmxvdwpUaGUgZ2lybGllcyBvbiBzdGFuZGJ5IHdhdmluZyBqdXN0IHRvIHNheSBoaQpEaWQgeW91IHN0b3A/IE5vLCBJIGp1c3QgZHJvdmUgYnkK");
+        let oracle = StaticOracle::new()
+            .with_random_prefix(64)
+            .with_suffix(&secret);
+        assert_eq!(attack_ecb_random_prefix(&oracle), Ok(secret));
+
+        let oracle = StaticOracle::<Toy64>::with_key(&random_key(8))
+            .with_random_prefix(20)
+            .with_prefix(b"fixed")
+            .with_suffix(b"attack at dawn");
+        assert_eq!(attack_ecb_random_prefix(&oracle), Ok(b"attack at dawn".to_vec()));
+    }
+
+    #[test]
+    fn test_ecb_cut_and_paste() {
+        let profile_manager = ProfileManager::new();
+        let forged = ecb_cut_and_paste(&profile_manager, b"user", b"admin").unwrap();
+        let cookie = profile_manager.decrypt_profile(&forged).unwrap();
+        assert!(ProfileManager::is_admin(&cookie));
+        assert_eq!(cookie.get("uid"), Some("10"));
+
+        assert_eq!(
+            ecb_cut_and_paste(&profile_manager, b"user", b"administrator!!!"),
+            Err(EcbAttackError::ReplacementTooLong)
+        );
+    }
+
+    #[test]
+    fn test_attack_ecb_errors() {
+        assert_eq!(
+            attack_ecb(&Challenge16::new()).err(),
+            Some(EcbAttackError::NotEcb)
+        );
+        assert_eq!(
+            attack_ecb(&CTROracle::new([0; 8])).err(),
+            Some(EcbAttackError::NotEcb)
+        );
+    }
+
+    #[test]
+    fn test_gcm_nonce_reuse_forgery() {
+        let gcm = Gcm::new(&random_key(16));
+        let nonce = b"reused nonce";
+        let messages = [
+            (&b"header"[..], &b"first message under this nonce"[..]),
+            (b"", b"a second, somewhat longer message under the same nonce"),
+            (b"header 3", b"and a third"),
+        ]
+        .map(|(aad, plain)| {
+            let (ciphertext, tag) = gcm.encrypt(nonce, aad, plain);
+            GcmMessage {
+                aad: aad.to_vec(),
+                ciphertext,
+                tag,
+            }
+        });
+
+        let candidates = gcm_nonce_reuse_hash_keys(&messages);
+        assert_eq!(candidates, vec![gcm.hash_key()]);
+
+        // forge attacker-chosen ciphertext without ever learning the key
+        let mut forged = messages[1].ciphertext.clone();
+        forged.xor(&b"ADMIN".to_vec());
+        let tag = gcm_forge_tag(candidates[0], &messages[0], b"evil header", &forged);
+        assert!(gcm.decrypt(nonce, b"evil header", &forged, &tag).is_ok());
+    }
+
+    #[test]
+    fn test_bitflip_inject_cbc_and_ctr() {
+        let cbc = Challenge16::new();
+        let forged = bitflip_inject(&cbc, b";admin=true;").unwrap();
+        assert!(cbc.is_admin(&forged));
+
+        let ctr = Challenge26::new();
+        let forged = bitflip_inject(&ctr, b";admin=true;").unwrap();
+        assert!(ctr.is_admin(&forged));
+    }
+
+    /// An 8-byte block cipher for exercising attacks at a non-AES block size.
+    struct Toy64 {
+        key: Vec<u8>,
+    }
+
+    impl BlockCipher for Toy64 {
+        const BLOCK_SIZE: usize = 8;
+
+        fn new(key: &[u8]) -> Self {
+            Toy64 { key: key.to_vec() }
+        }
+
+        fn encrypt_block(&self, block: &mut [u8]) {
+            for (b, k) in block.iter_mut().zip(&self.key) {
+                *b = (*b ^ k).rotate_left(3).wrapping_add(*k);
+            }
+            block.rotate_left(3);
+        }
+
+        fn decrypt_block(&self, block: &mut [u8]) {
+            block.rotate_right(3);
+            for (b, k) in block.iter_mut().zip(&self.key) {
+                *b = b.wrapping_sub(*k).rotate_right(3) ^ k;
+            }
+        }
+    }
+
+    struct PrefixedCbc {
+        prefix: Vec<u8>,
+        key: Vec<u8>,
+    }
+
+    impl Oracle for PrefixedCbc {
+        fn encrypt(&self, input: &[u8]) -> Vec<u8> {
+            cbc_encrypt(&[&self.prefix[..], input, b";suffix"].concat(), &self.key, &[0; 16])
+        }
+    }
+
+    #[test]
+    fn test_cbc_bitflip_inject_any_alignment() {
+        for prefix_length in 0..40 {
+            let oracle = PrefixedCbc {
+                prefix: vec![b'p'; prefix_length],
+                key: random_key(16),
+            };
+            assert_eq!(get_cbc_prefix_length(16, &oracle), Some(prefix_length));
+
+            let forged = cbc_bitflip_inject(&oracle, b";admin=true;").unwrap();
+            let plaintext = cbc_decrypt(forged, &oracle.key, &[0; 16]);
+            assert!(plaintext.windows(12).any(|w| w == b";admin=true;"));
+            assert_eq!(&plaintext[..prefix_length], &oracle.prefix[..]);
+        }
+    }
+
+    #[test]
+    fn test_bitflip_inject_cbc_target_too_long() {
+        let target = b";admin=true;role=admin;";
+        assert_eq!(
+            bitflip_inject(&Challenge16::new(), target),
+            Err(BitflipError::TargetTooLong)
+        );
+        let forged = bitflip_inject(&Challenge26::new(), target);
+        assert!(forged.is_ok());
+    }
+
+    #[test]
+    fn test_recover_key_as_iv() {
+        let oracle = Challenge27::new();
+        let key = recover_key_as_iv(&oracle).expect("Decryption did not leak the plaintext");
+
+        // with the key in hand, admin cookies can be minted directly
+        let forged = cbc_encrypt(b"userdata=x;admin=true;", &key, &key);
+        assert_eq!(oracle.is_admin(&forged), Ok(true));
+
+        let key = random_key(8);
+        let oracle = Challenge27::<Toy64>::with_key(&key);
+        assert_eq!(recover_key_as_iv(&oracle), Some(key));
+    }
+
+    #[test]
+    fn test_break_ctr_edit() {
+        let input = std::fs::read_to_string("data/7.txt").expect("Unable to read file");
+        let plaintext = decrypt_aes_128_padded(&Vec::<u8>::from_base64(&input), b"YELLOW SUBMARINE");
+        let oracle = CTROracle::new(rand::random());
+        let ciphertext = oracle.encrypt(&plaintext);
+
+        assert_eq!(break_ctr_edit(&oracle, &ciphertext), plaintext);
     }
 
     #[test]
     fn test_attack_single_block_padding_oracle() {
         let oracle = Challenge17::new();
         let input = "YELLOW SUBMARINE".as_bytes();
-        let (iv, cipher) = oracle.encrypt(&input);
+        let (iv, cipher) = oracle.encrypt(input);
         let test_block = cipher[0..16].to_vec();
-        let mut result = single_block_padding_attack(&test_block, &oracle);
+        let mut result = single_block_padding_attack(&test_block, &oracle).unwrap();
         result.xor(&iv);
         assert_eq!(result[15], input[15]);
     }
+
+    #[test]
+    fn test_oracle_padding_attack_strips_padding() {
+        let oracle = Challenge17::new();
+        let input = b"Cooking MC's like a pound of bacon";
+        let (iv, cipher) = oracle.encrypt(input);
+        assert_eq!(oracle_padding_attack_stripped(&iv, &cipher, &oracle), Ok(input.to_vec()));
+    }
+
+    #[test]
+    fn test_oracle_padding_attack_8_byte_blocks() {
+        let oracle = Challenge17::with_cipher(Toy64::new(&random_key(8)));
+        let input = b"attack at dawn";
+        let (iv, cipher) = oracle.encrypt(input);
+        assert_eq!(cipher.len(), 16);
+
+        let padded = oracle_padding_attack(&iv, &cipher, &oracle).unwrap();
+        assert_eq!(&padded[..], b"attack at dawn\x02\x02");
+    }
+
+    struct RejectEverything;
+
+    impl PaddingOracle for RejectEverything {
+        fn block_size(&self) -> usize {
+            16
+        }
+
+        fn is_valid_padding(&self, _iv: &[u8], _ciphertext: &[u8]) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_oracle_padding_attack_errors() {
+        assert_eq!(
+            oracle_padding_attack(&[0; 16], &[0; 20], &RejectEverything),
+            Err(PaddingOracleError::InvalidLength)
+        );
+        assert_eq!(
+            oracle_padding_attack(&[0; 16], &[0; 32], &RejectEverything),
+            Err(PaddingOracleError::NoValidPadding { block: 0, byte: 15 })
+        );
+    }
+
+    #[test]
+    fn test_padding_oracle_encrypt() {
+        let key = random_key(16);
+        let oracle = Challenge17::new_with_key(&key);
+        let plaintext = b"comment1=cooking%20MCs;userdata=x;admin=true";
+
+        let (iv, ciphertext) = padding_oracle_encrypt(plaintext, &oracle).unwrap();
+        assert!(oracle.is_valid_padding(&iv, &ciphertext));
+
+        let decrypted = strip_padding(cbc_decrypt(ciphertext, &key, &iv)).unwrap();
+        assert_eq!(decrypted, plaintext);
+    }
+
+    struct CountingOracle {
+        inner: Challenge17,
+        queries: AtomicUsize,
+    }
+
+    impl PaddingOracle for CountingOracle {
+        fn block_size(&self) -> usize {
+            self.inner.block_size()
+        }
+
+        fn is_valid_padding(&self, iv: &[u8], ciphertext: &[u8]) -> bool {
+            self.queries.fetch_add(1, Ordering::Relaxed);
+            self.inner.is_valid_padding(iv, ciphertext)
+        }
+    }
+
+    #[test]
+    fn test_parallel_padding_oracle_attack() {
+        let oracle = CountingOracle {
+            inner: Challenge17::new(),
+            queries: AtomicUsize::new(0),
+        };
+        let input = b"Now that the party is jumping, with the bass kicked in";
+        let (iv, cipher) = oracle.inner.encrypt(input);
+
+        let (padded, stats) = parallel_padding_oracle_attack(&iv, &cipher, &oracle).unwrap();
+        assert_eq!(strip_padding(padded).unwrap(), input);
+
+        assert_eq!(stats.queries.len(), 4);
+        assert_eq!(stats.total_queries(), oracle.queries.load(Ordering::Relaxed));
+        assert_eq!(
+            (0..4).map(|block| stats.block_queries(block)).sum::<usize>(),
+            stats.total_queries()
+        );
+        // English text should cost far less than the 128 queries per byte of a blind search
+        assert!(stats.total_queries() < cipher.len() * 32);
+        // once the last byte is known the rest of the padding takes a query each
+        assert!(stats.queries[3][6..15].iter().all(|&queries| queries == 1));
+    }
+
+    #[test]
+    fn test_timing_padding_oracle_attack_invalid_length() {
+        let oracle = TimedChallenge17::new();
+        let (iv, cipher) = oracle.encrypt(b"YELLOW SUBMARINE");
+        for (iv, cipher) in [(&iv[..], &cipher[..20]), (&iv[..8], &cipher[..]), (&iv[..], &[][..])] {
+            assert_eq!(
+                timing_padding_oracle_attack(iv, cipher, &oracle),
+                Err(PaddingOracleError::InvalidLength)
+            );
+        }
+    }
+
+    #[test]
+    fn test_timing_padding_oracle_attack() {
+        let oracle = TimedChallenge17::new().with_simulated_clock();
+        let input = b"I go crazy when I hear a cymbal";
+        let (iv, cipher) = oracle.encrypt(input);
+
+        let classifier = TimingClassifier::calibrate(&oracle, &iv, &cipher, 3).unwrap();
+        assert!(classifier.threshold() < Duration::from_millis(1));
+
+        assert_eq!(timing_padding_oracle_attack(&iv, &cipher, &oracle), Ok(input.to_vec()));
+    }
+
+    #[test]
+    #[ignore = "relies on 1ms wall-clock sleeps, which are unreliable under load"]
+    fn test_timing_padding_oracle_attack_wall_clock() {
+        let oracle = TimedChallenge17::new();
+        let input = b"I go crazy when I hear a cymbal";
+        let (iv, cipher) = oracle.encrypt(input);
+        assert_eq!(timing_padding_oracle_attack(&iv, &cipher, &oracle), Ok(input.to_vec()));
+    }
 }
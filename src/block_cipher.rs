@@ -0,0 +1,225 @@
+use aes::cipher::{generic_array::GenericArray, BlockDecrypt, BlockEncrypt, KeyInit};
+
+use crate::{
+    pkcs7::{self, StripPaddingError},
+    rijndael::Rijndael,
+};
+
+/// A keyed block cipher that transforms a single block in place.
+///
+/// Modes of operation (ECB, CBC, CTR, ...) are written against this trait so they
+/// can be driven by any cipher and key size instead of assuming AES-128.
+pub trait BlockCipher {
+    /// Size of a single block in bytes.
+    const BLOCK_SIZE: usize;
+
+    /// Runs the key schedule for `key`.
+    ///
+    /// Panics if the key has the wrong length for the cipher.
+    fn new(key: &[u8]) -> Self;
+
+    fn encrypt_block(&self, block: &mut [u8]);
+    fn decrypt_block(&self, block: &mut [u8]);
+}
+
+macro_rules! aes_block_cipher {
+    ($name:ident, $inner:ty, $key_size:expr) => {
+        pub struct $name {
+            cipher: $inner,
+        }
+
+        impl $name {
+            pub const KEY_SIZE: usize = $key_size;
+        }
+
+        impl BlockCipher for $name {
+            const BLOCK_SIZE: usize = 16;
+
+            fn new(key: &[u8]) -> Self {
+                assert_eq!(
+                    key.len(),
+                    Self::KEY_SIZE,
+                    concat!(stringify!($name), " requires a {} byte key"),
+                    Self::KEY_SIZE
+                );
+                Self {
+                    cipher: <$inner>::new(GenericArray::from_slice(key)),
+                }
+            }
+
+            fn encrypt_block(&self, block: &mut [u8]) {
+                self.cipher.encrypt_block(GenericArray::from_mut_slice(block));
+            }
+
+            fn decrypt_block(&self, block: &mut [u8]) {
+                self.cipher.decrypt_block(GenericArray::from_mut_slice(block));
+            }
+        }
+    };
+}
+
+aes_block_cipher!(Aes128, aes::Aes128, 16);
+aes_block_cipher!(Aes192, aes::Aes192, 24);
+aes_block_cipher!(Aes256, aes::Aes256, 32);
+
+/// The implementations that can back an [`Aes`] instance.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AesBackend {
+    /// The external `aes` crate, as used by `utils::encrypt_aes_128`.
+    Crate,
+    /// The in-crate AES with a lookup-table S-box, leaky under cache timing.
+    Table,
+    /// The in-crate AES with a computed S-box and no secret-dependent lookups.
+    ConstantTime,
+}
+
+enum AesImpl {
+    Crate128(Aes128),
+    Crate192(Aes192),
+    Crate256(Aes256),
+    Rijndael(Rijndael),
+}
+
+/// AES with any key size and a backend chosen at runtime.
+pub struct Aes {
+    backend: AesBackend,
+    inner: AesImpl,
+}
+
+impl Aes {
+    pub fn with_backend(key: &[u8], backend: AesBackend) -> Self {
+        let inner = match backend {
+            AesBackend::Crate => match key.len() {
+                16 => AesImpl::Crate128(Aes128::new(key)),
+                24 => AesImpl::Crate192(Aes192::new(key)),
+                32 => AesImpl::Crate256(Aes256::new(key)),
+                len => panic!("Invalid AES key length: {}", len),
+            },
+            AesBackend::Table => AesImpl::Rijndael(Rijndael::new(key)),
+            AesBackend::ConstantTime => AesImpl::Rijndael(Rijndael::new_constant_time(key)),
+        };
+        Aes { backend, inner }
+    }
+
+    pub fn backend(&self) -> AesBackend {
+        self.backend
+    }
+}
+
+impl BlockCipher for Aes {
+    const BLOCK_SIZE: usize = 16;
+
+    fn new(key: &[u8]) -> Self {
+        Aes::with_backend(key, AesBackend::Crate)
+    }
+
+    fn encrypt_block(&self, block: &mut [u8]) {
+        match &self.inner {
+            AesImpl::Crate128(cipher) => cipher.encrypt_block(block),
+            AesImpl::Crate192(cipher) => cipher.encrypt_block(block),
+            AesImpl::Crate256(cipher) => cipher.encrypt_block(block),
+            AesImpl::Rijndael(cipher) => cipher.encrypt_block(block),
+        }
+    }
+
+    fn decrypt_block(&self, block: &mut [u8]) {
+        match &self.inner {
+            AesImpl::Crate128(cipher) => cipher.decrypt_block(block),
+            AesImpl::Crate192(cipher) => cipher.decrypt_block(block),
+            AesImpl::Crate256(cipher) => cipher.decrypt_block(block),
+            AesImpl::Rijndael(cipher) => cipher.decrypt_block(block),
+        }
+    }
+}
+
+pub fn ecb_encrypt<C: BlockCipher>(cipher: &C, input: &[u8]) -> Vec<u8> {
+    let mut output = input.to_vec();
+    output
+        .chunks_mut(C::BLOCK_SIZE)
+        .for_each(|block| cipher.encrypt_block(block));
+    output
+}
+
+pub fn ecb_decrypt<C: BlockCipher>(cipher: &C, input: &[u8]) -> Vec<u8> {
+    let mut output = input.to_vec();
+    output
+        .chunks_mut(C::BLOCK_SIZE)
+        .for_each(|block| cipher.decrypt_block(block));
+    output
+}
+
+pub fn padded_ecb_encrypt<C: BlockCipher>(cipher: &C, input: &[u8]) -> Vec<u8> {
+    let padded = pkcs7::pad_to_blocksize(input.to_vec(), C::BLOCK_SIZE);
+    ecb_encrypt(cipher, &padded)
+}
+
+pub fn padded_ecb_decrypt<C: BlockCipher>(
+    cipher: &C,
+    input: &[u8],
+) -> Result<Vec<u8>, StripPaddingError> {
+    pkcs7::strip_padding(ecb_decrypt(cipher, input))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Hex;
+
+    // FIPS-197 appendix C
+    const PLAINTEXT: &str = "00112233445566778899aabbccddeeff";
+
+    fn check_vector<C: BlockCipher>(key: &str, expected: &str) {
+        let key = Vec::<u8>::from_hex(key);
+        let cipher = C::new(&key);
+        let encrypted = ecb_encrypt(&cipher, &Vec::<u8>::from_hex(PLAINTEXT));
+        assert_eq!(encrypted.to_hex(), expected);
+        assert_eq!(ecb_decrypt(&cipher, &encrypted).to_hex(), PLAINTEXT);
+    }
+
+    #[test]
+    fn test_aes_128() {
+        check_vector::<Aes128>(
+            "000102030405060708090a0b0c0d0e0f",
+            "69c4e0d86a7b0430d8cdb78070b4c55a",
+        );
+    }
+
+    #[test]
+    fn test_aes_192() {
+        check_vector::<Aes192>(
+            "000102030405060708090a0b0c0d0e0f1011121314151617",
+            "dda97ca4864cdfe06eaf70a0ec0d7191",
+        );
+    }
+
+    #[test]
+    fn test_aes_256() {
+        check_vector::<Aes256>(
+            "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+            "8ea2b7ca516745bfeafc49904b496089",
+        );
+    }
+
+    #[test]
+    fn test_aes_backends_agree() {
+        let key = Vec::<u8>::from_hex("000102030405060708090a0b0c0d0e0f1011121314151617");
+        for backend in [AesBackend::Crate, AesBackend::Table, AesBackend::ConstantTime] {
+            let cipher = Aes::with_backend(&key, backend);
+            assert_eq!(cipher.backend(), backend);
+            let encrypted = ecb_encrypt(&cipher, &Vec::<u8>::from_hex(PLAINTEXT));
+            assert_eq!(encrypted.to_hex(), "dda97ca4864cdfe06eaf70a0ec0d7191");
+            assert_eq!(ecb_decrypt(&cipher, &encrypted).to_hex(), PLAINTEXT);
+        }
+    }
+
+    #[test]
+    fn test_padded_ecb_round_trip() {
+        let cipher = Aes256::new(&[7; 32]);
+        let encrypted = padded_ecb_encrypt(&cipher, b"YELLOW SUBMARINE!");
+        assert_eq!(encrypted.len(), 32);
+        assert_eq!(
+            padded_ecb_decrypt(&cipher, &encrypted).unwrap(),
+            b"YELLOW SUBMARINE!"
+        );
+    }
+}
@@ -1,32 +1,41 @@
-use log::info;
+use crate::{
+    block_cipher::{Aes128, BlockCipher},
+    pkcs7,
+    utils::Xor,
+};
 
-use crate::{pkcs7, utils::{Xor, decrypt_aes_128, encrypt_aes_128, Hex}};
+pub fn cbc_encrypt(input: &[u8], key: &[u8], iv: &[u8]) -> Vec<u8> {
+    cbc_encrypt_with(&Aes128::new(key), input, iv)
+}
 
-const BLOCKSIZE: usize = 16;
+pub fn cbc_decrypt(cipher: Vec<u8>, key: &[u8], iv: &[u8]) -> Vec<u8> {
+    cbc_decrypt_with(&Aes128::new(key), cipher, iv)
+}
 
-pub fn cbc_encrypt(input: &[u8], key: &[u8], iv: &[u8]) -> Vec<u8> {
-    let mut padded = pkcs7::pad_to_blocksize(input.to_vec(), BLOCKSIZE);
-    info!("last char: {}", padded.last().unwrap());
-    let chunks = padded.chunks_mut(BLOCKSIZE);
+pub fn cbc_encrypt_with<C: BlockCipher>(cipher: &C, input: &[u8], iv: &[u8]) -> Vec<u8> {
+    let mut padded = pkcs7::pad_to_blocksize(input.to_vec(), C::BLOCK_SIZE);
+    let chunks = padded.chunks_mut(C::BLOCK_SIZE);
     let mut previous_ct = iv.to_vec();
 
     let mut output = Vec::new();
     for chunk in chunks {
-        let chunk_ived = chunk.xor(&previous_ct.to_vec());
-        previous_ct = encrypt_aes_128(chunk_ived, key);
+        let chunk_ived = chunk.xor(&previous_ct);
+        cipher.encrypt_block(chunk_ived);
+        previous_ct = chunk_ived.to_vec();
         output.extend_from_slice(&previous_ct);
     }
     output
 }
 
-pub fn cbc_decrypt(mut cipher: Vec<u8>, key: &[u8], iv: &[u8]) -> Vec<u8> {
-    let chunks = cipher.chunks_mut(BLOCKSIZE);
+pub fn cbc_decrypt_with<C: BlockCipher>(cipher: &C, mut input: Vec<u8>, iv: &[u8]) -> Vec<u8> {
+    let chunks = input.chunks_mut(C::BLOCK_SIZE);
     let mut previous_ct = iv.to_vec();
 
     let mut output = Vec::new();
     for chunk in chunks {
-        let mut chunk_ived = decrypt_aes_128(chunk, key);
-        output.extend_from_slice(chunk_ived.xor(&previous_ct.to_vec()));
+        let mut chunk_ived = chunk.to_vec();
+        cipher.decrypt_block(&mut chunk_ived);
+        output.extend_from_slice(chunk_ived.xor(&previous_ct));
         previous_ct = chunk.to_vec();
     }
     output
@@ -34,9 +43,8 @@ pub fn cbc_decrypt(mut cipher: Vec<u8>, key: &[u8], iv: &[u8]) -> Vec<u8> {
 
 #[cfg(test)]
 mod tests {
-    use log::info;
-
     use super::*;
+    use crate::{block_cipher::Aes256, utils::Hex};
 
     #[test]
     fn test_cbc_encrypt() {
@@ -46,5 +54,17 @@ mod tests {
 
         let encrypt = cbc_encrypt(input.as_bytes(), key.as_bytes(), &iv);
         println!("encrypt: {}", encrypt.to_hex());
+        let decrypt = cbc_decrypt(encrypt, key.as_bytes(), &iv);
+        assert_eq!(pkcs7::strip_padding(decrypt).unwrap(), input.as_bytes());
+    }
+
+    #[test]
+    fn test_cbc_aes_256() {
+        let cipher = Aes256::new(&[1; 32]);
+        let iv = [2; 16];
+        let encrypt = cbc_encrypt_with(&cipher, b"hello hello hello", &iv);
+        assert_eq!(encrypt.len(), 32);
+        let decrypt = cbc_decrypt_with(&cipher, encrypt, &iv);
+        assert_eq!(pkcs7::strip_padding(decrypt).unwrap(), b"hello hello hello");
     }
 }
@@ -0,0 +1,143 @@
+use crate::block_cipher::{Aes128, BlockCipher};
+
+/// The number of bits fed back into the shift register per step.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CfbSegment {
+    Bit,
+    Byte,
+    Block,
+}
+
+pub fn cfb_encrypt(input: &[u8], key: &[u8], iv: &[u8], segment: CfbSegment) -> Vec<u8> {
+    cfb_encrypt_with(&Aes128::new(key), input, iv, segment)
+}
+
+pub fn cfb_decrypt(input: &[u8], key: &[u8], iv: &[u8], segment: CfbSegment) -> Vec<u8> {
+    cfb_decrypt_with(&Aes128::new(key), input, iv, segment)
+}
+
+pub fn cfb_encrypt_with<C: BlockCipher>(
+    cipher: &C,
+    input: &[u8],
+    iv: &[u8],
+    segment: CfbSegment,
+) -> Vec<u8> {
+    cfb(cipher, input, iv, segment, true)
+}
+
+pub fn cfb_decrypt_with<C: BlockCipher>(
+    cipher: &C,
+    input: &[u8],
+    iv: &[u8],
+    segment: CfbSegment,
+) -> Vec<u8> {
+    cfb(cipher, input, iv, segment, false)
+}
+
+fn cfb<C: BlockCipher>(
+    cipher: &C,
+    input: &[u8],
+    iv: &[u8],
+    segment: CfbSegment,
+    encrypt: bool,
+) -> Vec<u8> {
+    let mut register = iv.to_vec();
+    let mut output = Vec::with_capacity(input.len());
+
+    match segment {
+        CfbSegment::Bit => {
+            for byte in input {
+                let mut out = 0;
+                for bit in (0..8).rev() {
+                    let mut keystream = register.clone();
+                    cipher.encrypt_block(&mut keystream);
+                    let in_bit = (byte >> bit) & 1;
+                    let out_bit = in_bit ^ (keystream[0] >> 7);
+                    out |= out_bit << bit;
+                    // feed the ciphertext bit back in
+                    let feedback = if encrypt { out_bit } else { in_bit };
+                    shift_left_one_bit(&mut register, feedback);
+                }
+                output.push(out);
+            }
+        }
+        CfbSegment::Byte => {
+            for byte in input {
+                let mut keystream = register.clone();
+                cipher.encrypt_block(&mut keystream);
+                let out = byte ^ keystream[0];
+                register.remove(0);
+                register.push(if encrypt { out } else { *byte });
+                output.push(out);
+            }
+        }
+        CfbSegment::Block => {
+            for block in input.chunks(C::BLOCK_SIZE) {
+                cipher.encrypt_block(&mut register);
+                let out = block
+                    .iter()
+                    .zip(register.iter())
+                    .map(|(a, b)| a ^ b)
+                    .collect::<Vec<_>>();
+                register = if encrypt { out.clone() } else { block.to_vec() };
+                output.extend_from_slice(&out);
+            }
+        }
+    }
+    output
+}
+
+fn shift_left_one_bit(register: &mut [u8], feedback: u8) {
+    for i in 0..register.len() {
+        let carry = register.get(i + 1).map_or(feedback, |next| next >> 7);
+        register[i] = (register[i] << 1) | carry;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Hex;
+
+    // NIST SP 800-38A F.3
+    const KEY: &str = "2b7e151628aed2a6abf7158809cf4f3c";
+    const IV: &str = "000102030405060708090a0b0c0d0e0f";
+    const PLAIN: &str = "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+                         30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710";
+
+    fn check_vector(segment: CfbSegment, plain: &str, expected: &str) {
+        let key = Vec::<u8>::from_hex(KEY);
+        let iv = Vec::<u8>::from_hex(IV);
+        let plain = Vec::<u8>::from_hex(plain);
+        let cipher = cfb_encrypt(&plain, &key, &iv, segment);
+        assert_eq!(cipher.to_hex(), expected);
+        assert_eq!(cfb_decrypt(&cipher, &key, &iv, segment), plain);
+    }
+
+    #[test]
+    fn test_cfb1() {
+        // F.3.1, 16 one-bit segments
+        check_vector(CfbSegment::Bit, &PLAIN[..4], "68b3");
+    }
+
+    #[test]
+    fn test_cfb8() {
+        // F.3.7
+        check_vector(
+            CfbSegment::Byte,
+            &PLAIN[..36],
+            "3b79424c9c0dd436bace9e0ed4586a4f32b9",
+        );
+    }
+
+    #[test]
+    fn test_cfb128() {
+        // F.3.13
+        check_vector(
+            CfbSegment::Block,
+            PLAIN,
+            "3b3fd92eb72dad20333449f8e83cfb4ac8a64537a0b3a93fcde3cdad9f1ce58b\
+             26751f67a3cbb140b1808cf187a4f4dfc04b05357c5d1c0eeac4c66f9ff7f2e6",
+        );
+    }
+}
@@ -4,7 +4,7 @@ use log::info;
 
 use crate::cbc::cbc_encrypt;
 use crate::cbc::cbc_decrypt;
-use crate::oracle::Oracle;
+use crate::oracle::{AdminOracle, Oracle};
 
 pub struct Challenge16 {
     key: Vec<u8>,
@@ -20,18 +20,39 @@ impl Challenge16 {
     }
 
     pub fn is_admin(&self, input: &[u8]) -> bool {
-        is_admin(input)
+        is_admin_with(input, &self.key, &self.iv)
+    }
+}
+
+impl Default for Challenge16 {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Oracle for Challenge16 {
     fn encrypt(&self, input: &[u8]) -> Vec<u8> {
-        encrypt(input)
+        encrypt_with(input, &self.key, &self.iv)
+    }
+}
+
+impl AdminOracle for Challenge16 {
+    fn is_admin(&self, ciphertext: &[u8]) -> bool {
+        is_admin_with(ciphertext, &self.key, &self.iv)
     }
 }
 
 
 pub fn encrypt(input: &[u8]) -> Vec<u8> {
+    encrypt_with(input, KEY, IV)
+}
+
+pub fn is_admin(input: &[u8]) -> bool {
+    is_admin_with(input, KEY, IV)
+}
+
+/// Quotes out `;` and `=` and wraps the input in the comment/userdata cookie.
+pub fn wrap_userdata(input: &[u8]) -> Vec<u8> {
     let prefix = "comment1=cooking%20MCs;userdata=";
     let suffix = ";comment2=%20like%20a%20pound%20of%20bacon";
     
@@ -39,15 +60,21 @@ pub fn encrypt(input: &[u8]) -> Vec<u8> {
     output.push_str(prefix);
     // convert input back to a string because my oracle trait is stupid and dont want to refactor
     let str = input.iter().map(|x| *x as char).collect::<String>();
-    output.push_str(&str.replace(";", "%3b").replace("=", "%3d"));
+    output.push_str(&str.replace(';', "%3b").replace('=', "%3d"));
     output.push_str(suffix);
-
-    cbc_encrypt(&output.as_bytes(), &KEY, IV)
+    output.into_bytes()
 }
 
-pub fn is_admin(input: &[u8]) -> bool {
-    let d = cbc_decrypt(input.to_vec(), &KEY, IV);
-    let a = d.iter().map(|x| *x as char).collect::<String>();
+pub fn contains_admin(plaintext: &[u8]) -> bool {
+    let a = plaintext.iter().map(|x| *x as char).collect::<String>();
     info!("result: {}", a);
     a.contains(";admin=true;")
 }
+
+fn encrypt_with(input: &[u8], key: &[u8], iv: &[u8]) -> Vec<u8> {
+    cbc_encrypt(&wrap_userdata(input), key, iv)
+}
+
+fn is_admin_with(input: &[u8], key: &[u8], iv: &[u8]) -> bool {
+    contains_admin(&cbc_decrypt(input.to_vec(), key, iv))
+}
@@ -1,10 +1,13 @@
 use crate::{
-    cbc::{cbc_encrypt, cbc_decrypt},
-    oracle::Oracle,
-    pkcs7::{self, strip_padding},
-    utils::{padded_encrypt_aes_128, random_key, Hex, Base64},
+    block_cipher::{Aes128, BlockCipher},
+    cbc::{cbc_decrypt_with, cbc_encrypt_with},
+    oracle::{PaddingOracle, TimingOracle},
+    pkcs7::strip_padding,
+    utils::{random_key, Base64},
 };
 
+use std::time::{Duration, Instant};
+
 const INPUTS: [&str; 10] = [
     "MDAwMDAwTm93IHRoYXQgdGhlIHBhcnR5IGlzIGp1bXBpbmc=",
     "MDAwMDAxV2l0aCB0aGUgYmFzcyBraWNrZWQgaW4gYW5kIHRoZSBWZWdhJ3MgYXJlIHB1bXBpbic=",
@@ -18,47 +21,136 @@ const INPUTS: [&str; 10] = [
     "MDAwMDA5aXRoIG15IHJhZy10b3AgZG93biBzbyBteSBoYWlyIGNhbiBibG93",
 ];
 
-const BLOCK_SIZE: usize = 16;
-
-pub struct Challenge17 {
-    key: Vec<u8>,
+pub struct Challenge17<C: BlockCipher = Aes128> {
+    cipher: C,
 }
 
 impl Challenge17 {
     pub fn new() -> Challenge17 {
-        Self {
-            key: random_key(BLOCK_SIZE),
-        }
+        Self::new_with_key(&random_key(Aes128::KEY_SIZE))
     }
     
     pub fn new_with_key(key: &[u8]) -> Challenge17 {
-        Self {
-            key: key.to_vec(),
-        }
+        Self::with_cipher(Aes128::new(key))
+    }
+}
+
+impl Default for Challenge17 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: BlockCipher> Challenge17<C> {
+    pub fn with_cipher(cipher: C) -> Self {
+        Self { cipher }
+    }
+
+    pub fn block_size(&self) -> usize {
+        C::BLOCK_SIZE
     }
 
     pub fn encrypt_random_input(&self) -> (Vec<u8>, Vec<u8>) {
         // select one of the input strings
         let input = Vec::<u8>::from_base64(INPUTS[rand::random::<usize>() % INPUTS.len()]);
         println!("input: {:?}", std::str::from_utf8(&input).unwrap());
-        let iv = random_key(BLOCK_SIZE);
-        let encrypted = cbc_encrypt(&input, &self.key, &iv);
-        (iv, encrypted)
+        self.encrypt(&input)
     }
 
     pub fn encrypt(&self, input: &[u8]) -> (Vec<u8>, Vec<u8>) {
-        // select one of the input strings
-        let iv = random_key(BLOCK_SIZE);
-        let encrypted = cbc_encrypt(input, &self.key, &iv);
+        let iv = random_key(C::BLOCK_SIZE);
+        let encrypted = cbc_encrypt_with(&self.cipher, input, &iv);
         (iv, encrypted)
     }
 
 
     pub fn is_valid_padding(&self, iv: &[u8], encrypted: &[u8]) -> bool {
-        let decrypted = cbc_decrypt(encrypted.to_vec(), &self.key, iv);
+        let decrypted = cbc_decrypt_with(&self.cipher, encrypted.to_vec(), iv);
         let strip_padding_result = strip_padding(decrypted);
         strip_padding_result.is_ok()
     }
 
 }
 
+impl<C: BlockCipher> PaddingOracle for Challenge17<C> {
+    fn block_size(&self) -> usize {
+        self.block_size()
+    }
+
+    fn is_valid_padding(&self, iv: &[u8], ciphertext: &[u8]) -> bool {
+        self.is_valid_padding(iv, ciphertext)
+    }
+}
+
+/// Challenge 17 without the boolean: messages are always rejected, but the MAC is
+/// only checked (taking `mac_delay`) when the padding is valid, Lucky 13 style.
+pub struct TimedChallenge17<C: BlockCipher = Aes128> {
+    inner: Challenge17<C>,
+    mac_delay: Duration,
+    simulated_clock: bool,
+}
+
+impl TimedChallenge17 {
+    pub fn new() -> Self {
+        Self::with_cipher(Aes128::new(&random_key(Aes128::KEY_SIZE)))
+    }
+}
+
+impl Default for TimedChallenge17 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: BlockCipher> TimedChallenge17<C> {
+    pub fn with_cipher(cipher: C) -> Self {
+        Self {
+            inner: Challenge17::with_cipher(cipher),
+            mac_delay: Duration::from_millis(1),
+            simulated_clock: false,
+        }
+    }
+
+    pub fn with_mac_delay(mut self, mac_delay: Duration) -> Self {
+        self.mac_delay = mac_delay;
+        self
+    }
+
+    /// Charges the MAC check to a simulated clock, with up to 10% jitter, instead
+    /// of sleeping, so the leak doesn't depend on the scheduler.
+    pub fn with_simulated_clock(mut self) -> Self {
+        self.simulated_clock = true;
+        self
+    }
+
+    pub fn encrypt(&self, input: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        self.inner.encrypt(input)
+    }
+}
+
+impl<C: BlockCipher> TimingOracle for TimedChallenge17<C> {
+    fn block_size(&self) -> usize {
+        C::BLOCK_SIZE
+    }
+
+    fn submit(&self, iv: &[u8], ciphertext: &[u8]) {
+        if self.inner.is_valid_padding(iv, ciphertext) && !self.simulated_clock {
+            // stands in for hashing the message; the tag never matches anyway
+            std::thread::sleep(self.mac_delay);
+        }
+    }
+
+    fn response_time(&self, iv: &[u8], ciphertext: &[u8]) -> Duration {
+        if !self.simulated_clock {
+            let start = Instant::now();
+            self.submit(iv, ciphertext);
+            return start.elapsed();
+        }
+        let jitter = self.mac_delay * (rand::random::<u32>() % 10) / 100;
+        if self.inner.is_valid_padding(iv, ciphertext) {
+            self.mac_delay + jitter
+        } else {
+            jitter
+        }
+    }
+}
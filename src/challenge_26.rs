@@ -0,0 +1,47 @@
+use crate::challenge_16::{contains_admin, wrap_userdata};
+use crate::ctr::CTROracle;
+use crate::oracle::{AdminOracle, Oracle};
+
+/// The challenge 16 comment/userdata cookie, encrypted with CTR instead of CBC.
+pub struct Challenge26 {
+    oracle: CTROracle,
+}
+
+impl Challenge26 {
+    pub fn new() -> Self {
+        Challenge26 {
+            oracle: CTROracle::new(rand::random()),
+        }
+    }
+}
+
+impl Default for Challenge26 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Oracle for Challenge26 {
+    fn encrypt(&self, input: &[u8]) -> Vec<u8> {
+        self.oracle.encrypt(&wrap_userdata(input))
+    }
+}
+
+impl AdminOracle for Challenge26 {
+    fn is_admin(&self, ciphertext: &[u8]) -> bool {
+        // CTR decryption is the same operation as encryption
+        contains_admin(&self.oracle.encrypt(ciphertext))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_is_quoted() {
+        let oracle = Challenge26::new();
+        let cipher = oracle.encrypt(b";admin=true;");
+        assert!(!oracle.is_admin(&cipher));
+    }
+}
@@ -0,0 +1,100 @@
+use crate::{
+    block_cipher::{Aes128, BlockCipher},
+    cbc::{cbc_decrypt_with, cbc_encrypt_with},
+    challenge_16::{contains_admin, wrap_userdata},
+    oracle::{KeyAsIvOracle, Oracle},
+    pkcs7::strip_padding,
+    utils::random_key,
+};
+
+#[derive(Debug, PartialEq)]
+pub enum DecryptError {
+    /// The plaintext contained bytes above 0x7f; the whole decryption is included,
+    /// as a careless server would put it in its error message.
+    HighAscii(Vec<u8>),
+    InvalidPadding,
+}
+
+/// The challenge 16 cookie oracle, but reusing the key as the CBC IV.
+pub struct Challenge27<C: BlockCipher = Aes128> {
+    cipher: C,
+    key: Vec<u8>,
+}
+
+impl Challenge27 {
+    pub fn new() -> Self {
+        Self::with_key(&random_key(Aes128::KEY_SIZE))
+    }
+}
+
+impl Default for Challenge27 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: BlockCipher> Challenge27<C> {
+    /// The key doubles as the IV, so it has to be exactly one block long.
+    pub fn with_key(key: &[u8]) -> Self {
+        assert_eq!(key.len(), C::BLOCK_SIZE, "The key must be one block long");
+        Challenge27 {
+            cipher: C::new(key),
+            key: key.to_vec(),
+        }
+    }
+
+    pub fn block_size(&self) -> usize {
+        C::BLOCK_SIZE
+    }
+
+    pub fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>, DecryptError> {
+        let plaintext = cbc_decrypt_with(&self.cipher, ciphertext.to_vec(), &self.key);
+        if !plaintext.is_ascii() {
+            return Err(DecryptError::HighAscii(plaintext));
+        }
+        strip_padding(plaintext).map_err(|_| DecryptError::InvalidPadding)
+    }
+
+    pub fn is_admin(&self, ciphertext: &[u8]) -> Result<bool, DecryptError> {
+        self.decrypt(ciphertext)
+            .map(|plaintext| contains_admin(&plaintext))
+    }
+}
+
+impl<C: BlockCipher> Oracle for Challenge27<C> {
+    fn encrypt(&self, input: &[u8]) -> Vec<u8> {
+        cbc_encrypt_with(&self.cipher, &wrap_userdata(input), &self.key)
+    }
+}
+
+impl<C: BlockCipher> KeyAsIvOracle for Challenge27<C> {
+    fn block_size(&self) -> usize {
+        self.block_size()
+    }
+
+    fn rejected_plaintext(&self, ciphertext: &[u8]) -> Option<Vec<u8>> {
+        match self.decrypt(ciphertext) {
+            Err(DecryptError::HighAscii(plaintext)) => Some(plaintext),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejects_high_ascii() {
+        let oracle = Challenge27::new();
+        let mut cipher = oracle.encrypt(b"hello");
+        assert_eq!(oracle.is_admin(&cipher), Ok(false));
+
+        // flipping the top bit of a byte in the first block sets it in the second
+        cipher[0] ^= 0x80;
+        assert!(matches!(
+            oracle.decrypt(&cipher),
+            Err(DecryptError::HighAscii(_))
+        ));
+    }
+}
@@ -1,37 +1,133 @@
-use std::{collections::HashMap, hash::Hash, str::from_utf8};
+use std::{fmt, str::FromStr};
 
-use itertools::Itertools;
-use log::info;
+use crate::{
+    block_cipher::{Aes128, BlockCipher},
+    oracle::{Oracle, StaticOracle},
+};
 
-use crate::oracle::{StaticOracle, Oracle};
+/// `key=value` pairs joined by `&`, kept in the order they were added.
+///
+/// `%`, `&` and `=` inside keys and values are percent escaped, so user input
+/// can't smuggle in extra fields.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Cookie {
+    fields: Vec<(String, String)>,
+}
 
 #[derive(Debug, PartialEq)]
-pub struct Cookie(pub HashMap<String, String>);
+pub enum CookieParseError {
+    /// A pair had no `=` separating key and value.
+    MissingEquals { pair: String },
+    /// A `%` was not followed by two hex digits, or decoded to invalid UTF-8.
+    InvalidEscape { pair: String },
+}
 
-fn encode(s: Cookie) -> String {
-    format!("email={}&uid={}&role={}", s.0["email"], s.0["uid"], s.0["role"])
-} 
+#[derive(Debug, PartialEq)]
+pub enum ProfileError {
+    /// The ciphertext was empty or not a whole number of blocks.
+    InvalidLength,
+    InvalidPadding,
+    InvalidUtf8,
+    Parse(CookieParseError),
+}
+
+impl Cookie {
+    pub fn new() -> Self {
+        Cookie { fields: vec![] }
+    }
+
+    /// The value of the first field named `key`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Replaces the first field named `key`, or appends it if there is none.
+    pub fn set(&mut self, key: &str, value: &str) {
+        match self.fields.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.fields.push((key.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '%' | '&' | '=' => format!("%{:02X}", c as u8),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+fn unescape(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut output = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            // from_str_radix would also accept a sign, e.g. "%+1"
+            let hex = bytes.get(i + 1..i + 3)?;
+            if !hex.iter().all(u8::is_ascii_hexdigit) {
+                return None;
+            }
+            output.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+            i += 3;
+        } else {
+            output.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(output).ok()
+}
+
+impl fmt::Display for Cookie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pairs = self
+            .fields
+            .iter()
+            .map(|(k, v)| format!("{}={}", escape(k), escape(v)))
+            .collect::<Vec<_>>();
+        write!(f, "{}", pairs.join("&"))
+    }
+}
 
 // foo=bar&baz=qux&zap=zazzle
-fn decode(s: &str) -> Cookie {
-    println!("s: {}", s);
-    let c = s.split("&").fold(HashMap::new(), |mut result, pair| {
-        let mut key_value = pair.split("=");
-        let key = key_value.next().unwrap();
-        let value = key_value.next().unwrap();
-        result.insert(key.to_string(), value.to_string());
-        result
-    });
-    Cookie(c)
+impl FromStr for Cookie {
+    type Err = CookieParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut cookie = Cookie::new();
+        if s.is_empty() {
+            return Ok(cookie);
+        }
+        for pair in s.split('&') {
+            let (key, value) = pair.split_once('=').ok_or(CookieParseError::MissingEquals {
+                pair: pair.to_string(),
+            })?;
+            let invalid_escape = || CookieParseError::InvalidEscape {
+                pair: pair.to_string(),
+            };
+            let key = unescape(key).ok_or_else(invalid_escape)?;
+            let value = unescape(value).ok_or_else(invalid_escape)?;
+            cookie.fields.push((key, value));
+        }
+        Ok(cookie)
+    }
 }
 
 fn profile_for(email: &str) -> Cookie {
-    let mut map = HashMap::new();
-    let sanitized_email = email.replace("&", "").replace("=", "");
-    map.insert("email".to_string(), sanitized_email.to_string());
-    map.insert("uid".to_string(), "10".to_string());
-    map.insert("role".to_string(), "user".to_string());
-    Cookie(map)
+    let mut cookie = Cookie::new();
+    cookie.set("email", email);
+    cookie.set("uid", "10");
+    cookie.set("role", "user");
+    cookie
 }
 
 pub struct ProfileManager {
@@ -47,24 +143,35 @@ impl ProfileManager {
 
     pub fn create_profile(&self, email: &str) -> Vec<u8> {
         let cookie = profile_for(email);
-        let encoded = encode(cookie);
-        self.oracle.encrypt(encoded.as_bytes())
+        self.oracle.encrypt(cookie.to_string().as_bytes())
     }
 
-    pub fn decrypt_profile(&self, ciphertext: &[u8]) -> Cookie {
-        let plaintext = self.oracle.decrypt(ciphertext);
-        decode(&String::from_utf8(plaintext).unwrap())
+    pub fn decrypt_profile(&self, ciphertext: &[u8]) -> Result<Cookie, ProfileError> {
+        if ciphertext.is_empty() || !ciphertext.len().is_multiple_of(Aes128::BLOCK_SIZE) {
+            return Err(ProfileError::InvalidLength);
+        }
+        let plaintext = self
+            .oracle
+            .try_decrypt(ciphertext)
+            .map_err(|_| ProfileError::InvalidPadding)?;
+        let plaintext = String::from_utf8(plaintext).map_err(|_| ProfileError::InvalidUtf8)?;
+        plaintext.parse().map_err(ProfileError::Parse)
     }
 
     pub fn is_admin(cookie: &Cookie) -> bool {
-        cookie.0.get("role").unwrap() == "admin"
+        cookie.get("role") == Some("admin")
+    }
+}
+
+impl Default for ProfileManager {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Oracle for ProfileManager {
     fn encrypt(&self, input: &[u8]) -> Vec<u8> {
-        println!("input: {:?}", input);
-        self.create_profile(from_utf8(input).unwrap())
+        self.create_profile(&String::from_utf8_lossy(input))
     }
 }
 
@@ -74,10 +181,93 @@ mod tests {
 
     #[test]
     fn test_decode() {
-        let mut map = HashMap::new();
-        map.insert("foo".to_string(), "bar".to_string());
-        map.insert("baz".to_string(), "qux".to_string());
-        map.insert("zap".to_string(), "zazzle".to_string());
-        assert_eq!(decode("foo=bar&baz=qux&zap=zazzle"), Cookie(map));
+        let cookie = "foo=bar&baz=qux&zap=zazzle".parse::<Cookie>().unwrap();
+        assert_eq!(
+            cookie.fields().collect::<Vec<_>>(),
+            vec![("foo", "bar"), ("baz", "qux"), ("zap", "zazzle")]
+        );
+        assert_eq!(cookie.get("baz"), Some("qux"));
+    }
+
+    #[test]
+    fn test_profile_for_escapes_metacharacters() {
+        let encoded = profile_for("foo@bar.com&role=admin").to_string();
+        assert_eq!(encoded, "email=foo@bar.com%26role%3Dadmin&uid=10&role=user");
+
+        let cookie = encoded.parse::<Cookie>().unwrap();
+        assert_eq!(cookie.get("email"), Some("foo@bar.com&role=admin"));
+        assert!(!ProfileManager::is_admin(&cookie));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            "email=a&uid".parse::<Cookie>(),
+            Err(CookieParseError::MissingEquals {
+                pair: "uid".to_string()
+            })
+        );
+        assert_eq!(
+            "email=a%2".parse::<Cookie>(),
+            Err(CookieParseError::InvalidEscape {
+                pair: "email=a%2".to_string()
+            })
+        );
+        for pair in ["a=%+1", "a=%-1"] {
+            assert_eq!(
+                pair.parse::<Cookie>(),
+                Err(CookieParseError::InvalidEscape {
+                    pair: pair.to_string()
+                })
+            );
+        }
+        assert_eq!(
+            "email=%FF".parse::<Cookie>(),
+            Err(CookieParseError::InvalidEscape {
+                pair: "email=%FF".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_round_trip_random_cookies() {
+        const ALPHABET: &[u8] = b"ab=&% 09@.";
+        let random_string = || {
+            let length = rand::random::<usize>() % 12;
+            (0..length)
+                .map(|_| ALPHABET[rand::random::<usize>() % ALPHABET.len()] as char)
+                .collect::<String>()
+        };
+        for _ in 0..500 {
+            let mut cookie = Cookie::new();
+            for _ in 0..rand::random::<usize>() % 5 {
+                cookie.set(&random_string(), &random_string());
+            }
+            assert_eq!(cookie.to_string().parse::<Cookie>(), Ok(cookie));
+        }
+    }
+
+    #[test]
+    fn test_decrypt_profile_errors() {
+        let profile_manager = ProfileManager::new();
+        let ciphertext = profile_manager.create_profile("foo@bar.com");
+        assert_eq!(
+            profile_manager.decrypt_profile(&ciphertext).unwrap().get("role"),
+            Some("user")
+        );
+
+        let mut truncated = ciphertext.clone();
+        truncated.truncate(16);
+        assert_eq!(
+            profile_manager.decrypt_profile(&truncated),
+            Err(ProfileError::InvalidPadding)
+        );
+
+        for length in [0, 5, 17] {
+            assert_eq!(
+                profile_manager.decrypt_profile(&ciphertext[..length]),
+                Err(ProfileError::InvalidLength)
+            );
+        }
     }
 }
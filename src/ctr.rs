@@ -1,49 +1,296 @@
-use crate::{utils::{encrypt_aes_128, Xor}, oracle::Oracle};
-use byteorder::{BigEndian, ByteOrder, LittleEndian, WriteBytesExt};
+use std::io::{self, Read, Seek, SeekFrom, Write};
 
-fn aes_ctr(input: &[u8], key: &[u8], nonce: &[u8]) -> Vec<u8> {
-    let mut output = Vec::new();
+use crate::{
+    block_cipher::{Aes128, BlockCipher},
+    oracle::{EditOracle, Oracle},
+};
 
-    let blocks = input.chunks(16);
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CounterEndian {
+    Little,
+    Big,
+}
+
+/// How a counter block is built: the nonce fills the front of the block and a
+/// `counter_size` byte counter fills the rest, wrapping within its own width.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CounterLayout {
+    pub counter_size: usize,
+    pub endian: CounterEndian,
+}
 
-    for (counter, block) in blocks.enumerate() {
-        // build a nonce block
-        let mut nonce_counter = Vec::new();
-        nonce_counter.extend_from_slice(&nonce[..]);
-        nonce_counter.write_u64::<LittleEndian>(counter as u64).expect("Unable to write");
+impl CounterLayout {
+    /// An 8 byte nonce and a little-endian u64 counter, as used by the challenges.
+    pub const LITTLE_ENDIAN_64: CounterLayout = CounterLayout {
+        counter_size: 8,
+        endian: CounterEndian::Little,
+    };
 
-        // encrypt nonce block with key
-        let mut block_keystream = encrypt_aes_128(&nonce_counter, key);
+    /// A 96-bit nonce and a big-endian u32 counter, as used by GCM.
+    pub const BIG_ENDIAN_32: CounterLayout = CounterLayout {
+        counter_size: 4,
+        endian: CounterEndian::Big,
+    };
 
+    /// The whole block is a big-endian counter, as in NIST SP 800-38A.
+    pub const BIG_ENDIAN_128: CounterLayout = CounterLayout {
+        counter_size: 16,
+        endian: CounterEndian::Big,
+    };
+
+    fn counter_block(&self, nonce: &[u8], counter: u128) -> Vec<u8> {
+        let mask = match self.counter_size {
+            16 => u128::MAX,
+            size => (1 << (8 * size)) - 1,
+        };
+        let counter = counter & mask;
+        let mut block = nonce.to_vec();
+        match self.endian {
+            CounterEndian::Little => {
+                block.extend_from_slice(&counter.to_le_bytes()[..self.counter_size])
+            }
+            CounterEndian::Big => {
+                block.extend_from_slice(&counter.to_be_bytes()[16 - self.counter_size..])
+            }
+        }
+        block
+    }
+}
+
+pub fn aes_ctr(input: &[u8], key: &[u8], nonce: &[u8]) -> Vec<u8> {
+    ctr_with(&Aes128::new(key), input, nonce)
+}
+
+/// CTR mode with the nonce followed by a little-endian u64 block counter.
+pub fn ctr_with<C: BlockCipher>(cipher: &C, input: &[u8], nonce: &[u8]) -> Vec<u8> {
+    ctr_with_layout(cipher, input, nonce, 0, CounterLayout::LITTLE_ENDIAN_64)
+}
+
+/// CTR mode with an arbitrary counter layout, starting from `initial_counter`.
+pub fn ctr_with_layout<C: BlockCipher>(
+    cipher: &C,
+    input: &[u8],
+    nonce: &[u8],
+    initial_counter: u128,
+    layout: CounterLayout,
+) -> Vec<u8> {
+    assert_eq!(
+        nonce.len() + layout.counter_size,
+        C::BLOCK_SIZE,
+        "nonce and counter must fill one cipher block"
+    );
+    let mut output = input.to_vec();
+    for (i, block) in output.chunks_mut(C::BLOCK_SIZE).enumerate() {
+        // encrypt nonce block with key
+        let mut keystream = layout.counter_block(nonce, initial_counter.wrapping_add(i as u128));
+        cipher.encrypt_block(&mut keystream);
         // xor the result against the input block
-        block_keystream.xor(&block.to_vec());
-        // append the result to the output
-        output.extend_from_slice(&block_keystream);
+        block.iter_mut().zip(keystream).for_each(|(b, k)| *b ^= k);
+    }
+    output
+}
+
+/// A CTR keystream that can be applied incrementally and repositioned at any
+/// byte offset, since every block of keystream is computed from its index alone.
+pub struct Ctr<C: BlockCipher = Aes128> {
+    cipher: C,
+    nonce: Vec<u8>,
+    initial_counter: u128,
+    layout: CounterLayout,
+    position: u64,
+    // the most recently generated keystream block and its index
+    cached: Option<(u64, Vec<u8>)>,
+}
+
+impl Ctr {
+    pub fn new(key: &[u8], nonce: &[u8]) -> Self {
+        Self::with_cipher(Aes128::new(key), nonce, CounterLayout::LITTLE_ENDIAN_64)
+    }
+}
+
+impl<C: BlockCipher> Ctr<C> {
+    pub fn with_cipher(cipher: C, nonce: &[u8], layout: CounterLayout) -> Self {
+        assert_eq!(
+            nonce.len() + layout.counter_size,
+            C::BLOCK_SIZE,
+            "nonce and counter must fill one cipher block"
+        );
+        Ctr {
+            cipher,
+            nonce: nonce.to_vec(),
+            initial_counter: 0,
+            layout,
+            position: 0,
+            cached: None,
+        }
+    }
+
+    /// Sets the counter value used for the block at offset 0.
+    pub fn with_initial_counter(mut self, initial_counter: u128) -> Self {
+        self.initial_counter = initial_counter;
+        self.cached = None;
+        self
     }
-    // trim the output to the length of the input
-    output[..input.len()].to_vec()
+
+    /// The byte offset the next call to `apply_keystream` will start at.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub fn seek_to(&mut self, offset: u64) {
+        self.position = offset;
+    }
+
+    /// XORs the keystream into `data` and advances the position past it.
+    pub fn apply_keystream(&mut self, data: &mut [u8]) {
+        let block_size = C::BLOCK_SIZE as u64;
+        for byte in data.iter_mut() {
+            let index = self.position / block_size;
+            let offset = (self.position % block_size) as usize;
+            *byte ^= self.keystream_block(index)[offset];
+            self.position += 1;
+        }
+    }
+
+    fn keystream_block(&mut self, index: u64) -> &[u8] {
+        if self.cached.as_ref().map(|(i, _)| *i) != Some(index) {
+            let counter = self.initial_counter.wrapping_add(index as u128);
+            let mut block = self.layout.counter_block(&self.nonce, counter);
+            self.cipher.encrypt_block(&mut block);
+            self.cached = Some((index, block));
+        }
+        &self.cached.as_ref().unwrap().1
+    }
+}
+
+/// Decrypts (or encrypts) everything read from `inner`.
+pub struct CtrReader<R, C: BlockCipher = Aes128> {
+    inner: R,
+    ctr: Ctr<C>,
 }
 
-pub struct CTROracle {
-    key: Vec<u8>,
-    nonce: [u8; 8],
+impl<R, C: BlockCipher> CtrReader<R, C> {
+    pub fn new(inner: R, ctr: Ctr<C>) -> Self {
+        CtrReader { inner, ctr }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read, C: BlockCipher> Read for CtrReader<R, C> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.ctr.apply_keystream(&mut buf[..read]);
+        Ok(read)
+    }
+}
+
+impl<R: Seek, C: BlockCipher> Seek for CtrReader<R, C> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = self.inner.seek(pos)?;
+        self.ctr.seek_to(position);
+        Ok(position)
+    }
+}
+
+/// Encrypts (or decrypts) everything written before passing it on to `inner`.
+pub struct CtrWriter<W, C: BlockCipher = Aes128> {
+    inner: W,
+    ctr: Ctr<C>,
+}
+
+impl<W, C: BlockCipher> CtrWriter<W, C> {
+    pub fn new(inner: W, ctr: Ctr<C>) -> Self {
+        CtrWriter { inner, ctr }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write, C: BlockCipher> Write for CtrWriter<W, C> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut encrypted = buf.to_vec();
+        // keep the keystream in step with what the inner writer actually accepted
+        let start = self.ctr.position();
+        self.ctr.apply_keystream(&mut encrypted);
+        let written = self.inner.write(&encrypted)?;
+        self.ctr.seek_to(start + written as u64);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Seek, C: BlockCipher> Seek for CtrWriter<W, C> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = self.inner.seek(pos)?;
+        self.ctr.seek_to(position);
+        Ok(position)
+    }
+}
+
+pub struct CTROracle<C: BlockCipher = Aes128> {
+    cipher: C,
+    nonce: Vec<u8>,
 }
 
 impl CTROracle {
     pub fn new(nonce: [u8; 8]) -> Self {
-        let key = crate::utils::random_key(16);
-        CTROracle { key, nonce }
+        let key = crate::utils::random_key(Aes128::KEY_SIZE);
+        Self::with_key(&key, &nonce)
     }
 }
 
-impl Oracle for CTROracle {
+impl<C: BlockCipher> CTROracle<C> {
+    /// The nonce and the 8-byte counter together must fill one cipher block.
+    pub fn with_key(key: &[u8], nonce: &[u8]) -> Self {
+        assert_eq!(nonce.len() + 8, C::BLOCK_SIZE, "nonce must leave room for the counter");
+        CTROracle {
+            cipher: C::new(key),
+            nonce: nonce.to_vec(),
+        }
+    }
+}
+
+impl<C: BlockCipher> Oracle for CTROracle<C> {
     fn encrypt(&self, input: &[u8]) -> Vec<u8> {
-        return aes_ctr(input, &self.key, &self.nonce);
+        ctr_with(&self.cipher, input, &self.nonce)
+    }
+}
+
+impl<C: BlockCipher> EditOracle for CTROracle<C> {
+    fn edit(&self, ciphertext: &[u8], offset: usize, newtext: &[u8]) -> Vec<u8> {
+        assert!(offset <= ciphertext.len(), "Edit offset past the end of the ciphertext");
+        // only generate keystream from the block containing the offset onwards
+        let first_block = offset / C::BLOCK_SIZE;
+        let skip = offset % C::BLOCK_SIZE;
+        let mut window = vec![0; skip];
+        window.extend_from_slice(newtext);
+        let encrypted = ctr_with_layout(
+            &self.cipher,
+            &window,
+            &self.nonce,
+            first_block as u128,
+            CounterLayout::LITTLE_ENDIAN_64,
+        );
+
+        let mut output = ciphertext.to_vec();
+        let end = (offset + newtext.len()).min(output.len());
+        output.splice(offset..end, encrypted[skip..].iter().copied());
+        output
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::io::Cursor;
+
     use crate::utils::{Base64, Hex};
 
     use super::*;
@@ -72,4 +319,89 @@ mod tests {
             aes_ctr(&cipher, key, &nonce)
         );
     }
+
+    #[test]
+    fn test_ctr_sp800_38a() {
+        // NIST SP 800-38A F.5.1, a full-block big-endian counter
+        let key = Vec::<u8>::from_hex("2b7e151628aed2a6abf7158809cf4f3c");
+        let initial = u128::from_str_radix("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff", 16).unwrap();
+        let plain = Vec::<u8>::from_hex(
+            "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+             30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710",
+        );
+        let cipher = ctr_with_layout(
+            &Aes128::new(&key),
+            &plain,
+            &[],
+            initial,
+            CounterLayout::BIG_ENDIAN_128,
+        );
+        assert_eq!(
+            cipher.to_hex(),
+            "874d6191b620e3261bef6864990db6ce9806f66b7970fdff8617187bb9fffdff\
+             5ae4df3edbd5d35e5b4f09020db03eab1e031dda2fbe03d1792170a0f3009cee"
+        );
+    }
+
+    #[test]
+    fn test_ctr_counter_wraps_within_its_width() {
+        let layout = CounterLayout::BIG_ENDIAN_32;
+        assert_eq!(
+            layout.counter_block(&[0xaa; 12], 0x1_0000_0001),
+            [vec![0xaa; 12], vec![0, 0, 0, 1]].concat()
+        );
+    }
+
+    #[test]
+    fn test_ctr_writer_and_reader() {
+        let key = b"YELLOW SUBMARINE";
+        let nonce = [7u8; 8];
+        let plain = b"Burning 'em, if you ain't quick and nimble I go crazy when I hear a cymbal";
+
+        let mut writer = CtrWriter::new(Vec::new(), Ctr::new(key, &nonce));
+        for chunk in plain.chunks(5) {
+            writer.write_all(chunk).unwrap();
+        }
+        let encrypted = writer.into_inner();
+        assert_eq!(encrypted, aes_ctr(plain, key, &nonce));
+
+        let mut reader = CtrReader::new(Cursor::new(encrypted), Ctr::new(key, &nonce));
+        let mut decrypted = vec![];
+        reader.read_to_end(&mut decrypted).unwrap();
+        assert_eq!(decrypted, plain);
+    }
+
+    #[test]
+    fn test_ctr_seek() {
+        let key = b"YELLOW SUBMARINE";
+        let nonce = [7u8; 8];
+        let plain = b"Burning 'em, if you ain't quick and nimble I go crazy when I hear a cymbal";
+        let encrypted = aes_ctr(plain, key, &nonce);
+
+        let mut reader = CtrReader::new(Cursor::new(encrypted), Ctr::new(key, &nonce));
+        reader.seek(SeekFrom::Start(37)).unwrap();
+        let mut buf = [0; 10];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, &plain[37..47]);
+
+        reader.seek(SeekFrom::Current(-20)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, &plain[27..37]);
+    }
+
+    #[test]
+    fn test_ctr_oracle_edit() {
+        let oracle = CTROracle::new([3; 8]);
+        let plain = b"Burning 'em, if you ain't quick and nimble I go crazy when I hear a cymbal";
+        let cipher = oracle.encrypt(plain);
+
+        let edited = oracle.edit(&cipher, 21, b"AIN'T");
+        let mut expected = plain.to_vec();
+        expected[21..26].copy_from_slice(b"AIN'T");
+        assert_eq!(edited, oracle.encrypt(&expected));
+
+        // edits running off the end extend the ciphertext
+        let extended = oracle.edit(&cipher, plain.len() - 6, b"cymbals!");
+        assert_eq!(extended, oracle.encrypt(&[&plain[..plain.len() - 6], b"cymbals!"].concat()));
+    }
 }
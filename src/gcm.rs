@@ -0,0 +1,196 @@
+use crate::{
+    block_cipher::{Aes128, BlockCipher},
+    ctr::{ctr_with_layout, CounterLayout},
+    gf128::{ghash, Gf128},
+};
+
+#[derive(Debug, PartialEq)]
+pub enum GcmError {
+    InvalidTag,
+}
+
+/// AES-GCM authenticated encryption with 128-bit tags.
+pub struct Gcm<C: BlockCipher = Aes128> {
+    cipher: C,
+    h: Gf128,
+}
+
+impl Gcm {
+    pub fn new(key: &[u8]) -> Self {
+        Self::with_cipher(Aes128::new(key))
+    }
+}
+
+impl<C: BlockCipher> Gcm<C> {
+    pub fn with_cipher(cipher: C) -> Self {
+        assert_eq!(C::BLOCK_SIZE, 16, "GCM is only defined for 128-bit blocks");
+        let mut h = [0; 16];
+        cipher.encrypt_block(&mut h);
+        Gcm {
+            cipher,
+            h: Gf128::from_bytes(&h),
+        }
+    }
+
+    /// The GHASH key H = E(K, 0^128).
+    pub fn hash_key(&self) -> Gf128 {
+        self.h
+    }
+
+    /// Returns the ciphertext and the authentication tag.
+    pub fn encrypt(&self, nonce: &[u8], aad: &[u8], plaintext: &[u8]) -> (Vec<u8>, [u8; 16]) {
+        let j0 = self.pre_counter_block(nonce);
+        let ciphertext = self.gctr(j0, plaintext);
+        let tag = self.tag(j0, aad, &ciphertext);
+        (ciphertext, tag)
+    }
+
+    pub fn decrypt(
+        &self,
+        nonce: &[u8],
+        aad: &[u8],
+        ciphertext: &[u8],
+        tag: &[u8],
+    ) -> Result<Vec<u8>, GcmError> {
+        let j0 = self.pre_counter_block(nonce);
+        let expected = self.tag(j0, aad, ciphertext);
+        // compare every byte so the check takes the same time wherever it fails
+        let difference = expected
+            .iter()
+            .zip(tag.iter())
+            .fold(0, |acc, (a, b)| acc | (a ^ b));
+        if tag.len() != expected.len() || difference != 0 {
+            return Err(GcmError::InvalidTag);
+        }
+        Ok(self.gctr(j0, ciphertext))
+    }
+
+    /// CTR encryption starting from the block after J0.
+    fn gctr(&self, j0: [u8; 16], input: &[u8]) -> Vec<u8> {
+        let counter = u32::from_be_bytes(j0[12..].try_into().unwrap());
+        ctr_with_layout(
+            &self.cipher,
+            input,
+            &j0[..12],
+            counter as u128 + 1,
+            CounterLayout::BIG_ENDIAN_32,
+        )
+    }
+
+    fn pre_counter_block(&self, nonce: &[u8]) -> [u8; 16] {
+        if nonce.len() == 12 {
+            let mut j0 = [0; 16];
+            j0[..12].copy_from_slice(nonce);
+            j0[15] = 1;
+            j0
+        } else {
+            // other nonce lengths are hashed down to a block
+            ghash(self.h, &[], nonce).to_bytes()
+        }
+    }
+
+    fn tag(&self, j0: [u8; 16], aad: &[u8], ciphertext: &[u8]) -> [u8; 16] {
+        let mut mask = j0;
+        self.cipher.encrypt_block(&mut mask);
+        (ghash(self.h, aad, ciphertext) + Gf128::from_bytes(&mask)).to_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Hex;
+
+    // test cases from "The Galois/Counter Mode of Operation (GCM)", McGrew and Viega
+    const KEY: &str = "feffe9928665731c6d6a8f9467308308";
+    const PLAIN: &str = "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a72\
+                         1c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39";
+    const AAD: &str = "feedfacedeadbeeffeedfacedeadbeefabaddad2";
+
+    fn check_vector(key: &str, nonce: &str, aad: &str, plain: &str, cipher: &str, tag: &str) {
+        let gcm = Gcm::new(&Vec::<u8>::from_hex(key));
+        let nonce = Vec::<u8>::from_hex(nonce);
+        let aad = Vec::<u8>::from_hex(aad);
+        let plain = Vec::<u8>::from_hex(plain);
+
+        let (encrypted, computed_tag) = gcm.encrypt(&nonce, &aad, &plain);
+        assert_eq!(encrypted.to_hex(), cipher);
+        assert_eq!(computed_tag.to_vec().to_hex(), tag);
+        assert_eq!(gcm.decrypt(&nonce, &aad, &encrypted, &computed_tag), Ok(plain));
+    }
+
+    #[test]
+    fn test_gcm_empty() {
+        check_vector(
+            &"00".repeat(16),
+            &"00".repeat(12),
+            "",
+            "",
+            "",
+            "58e2fccefa7e3061367f1d57a4e7455a",
+        );
+    }
+
+    #[test]
+    fn test_gcm_single_block() {
+        check_vector(
+            &"00".repeat(16),
+            &"00".repeat(12),
+            "",
+            &"00".repeat(16),
+            "0388dace60b6a392f328c2b971b2fe78",
+            "ab6e47d42cec13bdf53a67b21257bddf",
+        );
+    }
+
+    #[test]
+    fn test_gcm_with_aad() {
+        check_vector(
+            KEY,
+            "cafebabefacedbaddecaf888",
+            AAD,
+            PLAIN,
+            "42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e\
+             21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091",
+            "5bc94fbc3221a5db94fae95ae7121a47",
+        );
+    }
+
+    #[test]
+    fn test_gcm_64_bit_nonce() {
+        check_vector(
+            KEY,
+            "cafebabefacedbad",
+            AAD,
+            PLAIN,
+            "61353b4c2806934a777ff51fa22a4755699b2a714fcdc6f83766e5f97b6c7423\
+             73806900e49f24b22b097544d4896b424989b5e1ebac0f07c23f4598",
+            "3612d2e79e3b0785561be14aaca2fccb",
+        );
+    }
+
+    #[test]
+    fn test_gcm_long_nonce() {
+        check_vector(
+            KEY,
+            "9313225df88406e555909c5aff5269aa6a7a9538534f7da1e4c303d2a318a728\
+             c3c0c95156809539fcf0e2429a6b525416aedbf5a0de6a57a637b39b",
+            AAD,
+            PLAIN,
+            "8ce24998625615b603a033aca13fb894be9112a5c3a211a8ba262a3cca7e2ca7\
+             01e4a9a4fba43c90ccdcb281d48c7c6fd62875d2aca417034c34aee5",
+            "619cc5aefffe0bfa462af43c1699d050",
+        );
+    }
+
+    #[test]
+    fn test_gcm_rejects_forgery() {
+        let gcm = Gcm::new(b"YELLOW SUBMARINE");
+        let (mut cipher, tag) = gcm.encrypt(b"unique nonce", b"header", b"attack at dawn");
+        cipher[0] ^= 1;
+        assert_eq!(
+            gcm.decrypt(b"unique nonce", b"header", &cipher, &tag),
+            Err(GcmError::InvalidTag)
+        );
+    }
+}
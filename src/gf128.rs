@@ -0,0 +1,165 @@
+//! Arithmetic in GF(2^128) as used by GHASH.
+//!
+//! GCM stores field elements "bit-reflected": the most significant bit of the first
+//! byte of a block is the coefficient of x^0, so a block read as a big-endian `u128`
+//! has the coefficient of x^i in bit `127 - i`.
+
+use std::ops::{Add, AddAssign, Mul, MulAssign};
+
+/// Reduction constant for x^128 + x^7 + x^2 + x + 1, in GCM bit order.
+const R: u128 = 0xe1 << 120;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct Gf128(pub u128);
+
+impl Gf128 {
+    pub const ZERO: Gf128 = Gf128(0);
+    pub const ONE: Gf128 = Gf128(1 << 127);
+
+    /// Reads a block, zero-padding it on the right if it is shorter than 16 bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut block = [0; 16];
+        block[..bytes.len()].copy_from_slice(bytes);
+        Gf128(u128::from_be_bytes(block))
+    }
+
+    pub fn to_bytes(self) -> [u8; 16] {
+        self.0.to_be_bytes()
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn square(self) -> Self {
+        self * self
+    }
+
+    /// The unique square root, a^(2^127); squaring is a bijection in characteristic 2.
+    pub fn sqrt(self) -> Self {
+        (0..127).fold(self, |acc, _| acc.square())
+    }
+
+    pub fn pow(self, mut exponent: u128) -> Self {
+        let mut result = Gf128::ONE;
+        let mut base = self;
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result *= base;
+            }
+            base = base.square();
+            exponent >>= 1;
+        }
+        result
+    }
+
+    /// The multiplicative inverse, a^(2^128 - 2). Zero has no inverse.
+    pub fn inverse(self) -> Option<Self> {
+        if self.is_zero() {
+            None
+        } else {
+            Some(self.pow(u128::MAX - 1))
+        }
+    }
+}
+
+impl Add for Gf128 {
+    type Output = Gf128;
+
+    // addition in characteristic 2 is xor, so it is also subtraction
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn add(self, other: Self) -> Self {
+        Gf128(self.0 ^ other.0)
+    }
+}
+
+impl AddAssign for Gf128 {
+    #[allow(clippy::suspicious_op_assign_impl)]
+    fn add_assign(&mut self, other: Self) {
+        self.0 ^= other.0;
+    }
+}
+
+impl Mul for Gf128 {
+    type Output = Gf128;
+
+    // NIST SP 800-38D algorithm 1
+    fn mul(self, other: Self) -> Self {
+        let mut z = 0;
+        let mut v = other.0;
+        for i in (0..128).rev() {
+            if (self.0 >> i) & 1 == 1 {
+                z ^= v;
+            }
+            v = if v & 1 == 1 { (v >> 1) ^ R } else { v >> 1 };
+        }
+        Gf128(z)
+    }
+}
+
+impl MulAssign for Gf128 {
+    fn mul_assign(&mut self, other: Self) {
+        *self = *self * other;
+    }
+}
+
+/// GHASH over `data`, zero-padding the final partial block.
+pub fn ghash_blocks(h: Gf128, data: &[u8]) -> Gf128 {
+    data.chunks(16)
+        .fold(Gf128::ZERO, |y, block| (y + Gf128::from_bytes(block)) * h)
+}
+
+/// GHASH as used by GCM: the padded AAD, the padded ciphertext and then a block
+/// holding both of their lengths in bits.
+pub fn ghash(h: Gf128, aad: &[u8], ciphertext: &[u8]) -> Gf128 {
+    let mut data = aad.to_vec();
+    data.resize(aad.len().div_ceil(16) * 16, 0);
+    data.extend_from_slice(ciphertext);
+    data.resize(data.len() + (16 - ciphertext.len() % 16) % 16, 0);
+    data.extend_from_slice(&((aad.len() as u64) * 8).to_be_bytes());
+    data.extend_from_slice(&((ciphertext.len() as u64) * 8).to_be_bytes());
+    ghash_blocks(h, &data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Hex;
+
+    fn element(hex: &str) -> Gf128 {
+        Gf128::from_bytes(&Vec::<u8>::from_hex(hex))
+    }
+
+    #[test]
+    fn test_multiply() {
+        // GCM spec test case 2: GHASH(H, {}, C) with a single ciphertext block
+        let h = element("66e94bd4ef8a2c3b884cfa59ca342b2e");
+        let c = element("0388dace60b6a392f328c2b971b2fe78");
+        assert_eq!(c * h, element("5e2ec746917062882c85b0685353deb7"));
+        assert_eq!(h * Gf128::ONE, h);
+        assert_eq!(h * c, c * h);
+    }
+
+    #[test]
+    fn test_inverse() {
+        let h = element("66e94bd4ef8a2c3b884cfa59ca342b2e");
+        assert_eq!(h * h.inverse().unwrap(), Gf128::ONE);
+        assert_eq!(Gf128::ZERO.inverse(), None);
+    }
+
+    #[test]
+    fn test_sqrt() {
+        let h = element("66e94bd4ef8a2c3b884cfa59ca342b2e");
+        assert_eq!(h.square().sqrt(), h);
+    }
+
+    #[test]
+    fn test_ghash() {
+        let h = element("66e94bd4ef8a2c3b884cfa59ca342b2e");
+        let c = Vec::<u8>::from_hex("0388dace60b6a392f328c2b971b2fe78");
+        assert_eq!(
+            ghash(h, &[], &c).to_bytes().to_vec().to_hex(),
+            "f38cbb1ad69223dcc3457ae5b6b0f885"
+        );
+    }
+}
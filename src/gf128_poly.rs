@@ -0,0 +1,341 @@
+//! Polynomials over GF(2^128) and their factorisation.
+//!
+//! This is the machinery behind the GCM "forbidden attack": the GHASH key is a root
+//! of the difference of two tag polynomials that share a nonce, and the roots are
+//! found by Cantor–Zassenhaus factorisation.
+
+use std::ops::{Add, Mul};
+
+use crate::gf128::Gf128;
+
+/// Bits in a field element, so the field has q = 2^128 elements.
+const FIELD_BITS: usize = 128;
+
+/// A polynomial with coefficients stored lowest degree first and no trailing zeros.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Poly {
+    coefficients: Vec<Gf128>,
+}
+
+impl Poly {
+    pub fn new(mut coefficients: Vec<Gf128>) -> Self {
+        while coefficients.last().is_some_and(|c| c.is_zero()) {
+            coefficients.pop();
+        }
+        Poly { coefficients }
+    }
+
+    pub fn zero() -> Self {
+        Poly::new(vec![])
+    }
+
+    pub fn constant(c: Gf128) -> Self {
+        Poly::new(vec![c])
+    }
+
+    pub fn one() -> Self {
+        Poly::constant(Gf128::ONE)
+    }
+
+    /// The polynomial x.
+    pub fn x() -> Self {
+        Poly::new(vec![Gf128::ZERO, Gf128::ONE])
+    }
+
+    fn random(degree_below: usize) -> Self {
+        Poly::new(
+            (0..degree_below)
+                .map(|_| Gf128(rand::random::<u128>()))
+                .collect(),
+        )
+    }
+
+    pub fn coefficients(&self) -> &[Gf128] {
+        &self.coefficients
+    }
+
+    /// The degree, or `None` for the zero polynomial.
+    pub fn degree(&self) -> Option<usize> {
+        self.coefficients.len().checked_sub(1)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.coefficients.is_empty()
+    }
+
+    pub fn is_one(&self) -> bool {
+        *self == Poly::one()
+    }
+
+    pub fn leading(&self) -> Gf128 {
+        self.coefficients.last().copied().unwrap_or(Gf128::ZERO)
+    }
+
+    pub fn monic(&self) -> Self {
+        match self.leading().inverse() {
+            Some(inverse) => self * &Poly::constant(inverse),
+            None => self.clone(),
+        }
+    }
+
+    pub fn evaluate(&self, x: Gf128) -> Gf128 {
+        self.coefficients
+            .iter()
+            .rev()
+            .fold(Gf128::ZERO, |acc, c| acc * x + *c)
+    }
+
+    /// Polynomial long division, returning `(quotient, remainder)`.
+    pub fn div_rem(&self, divisor: &Poly) -> (Poly, Poly) {
+        let divisor_degree = divisor.degree().expect("Division by the zero polynomial");
+        let lead_inverse = divisor.leading().inverse().unwrap();
+
+        let mut remainder = self.coefficients.clone();
+        let mut quotient = vec![Gf128::ZERO; remainder.len().saturating_sub(divisor_degree)];
+        while remainder.len() > divisor_degree {
+            let shift = remainder.len() - 1 - divisor_degree;
+            let factor = *remainder.last().unwrap() * lead_inverse;
+            quotient[shift] = factor;
+            for (i, c) in divisor.coefficients.iter().enumerate() {
+                remainder[shift + i] += *c * factor;
+            }
+            remainder.pop();
+        }
+        (Poly::new(quotient), Poly::new(remainder))
+    }
+
+    pub fn rem(&self, modulus: &Poly) -> Poly {
+        self.div_rem(modulus).1
+    }
+
+    /// The monic greatest common divisor.
+    pub fn gcd(&self, other: &Poly) -> Poly {
+        let (mut a, mut b) = (self.clone(), other.clone());
+        while !b.is_zero() {
+            let r = a.rem(&b);
+            a = b;
+            b = r;
+        }
+        a.monic()
+    }
+
+    pub fn derivative(&self) -> Poly {
+        // in characteristic 2 only the odd powers survive, with coefficient c * 1
+        Poly::new(
+            self.coefficients
+                .iter()
+                .enumerate()
+                .skip(1)
+                .map(|(i, c)| if i % 2 == 1 { *c } else { Gf128::ZERO })
+                .collect(),
+        )
+    }
+
+    /// The square root of a polynomial whose odd coefficients are all zero.
+    fn sqrt(&self) -> Poly {
+        Poly::new(self.coefficients.iter().step_by(2).map(|c| c.sqrt()).collect())
+    }
+
+    /// Raises to the power q^`times` = 2^(128 * `times`) modulo `modulus`.
+    fn frobenius(&self, times: usize, modulus: &Poly) -> Poly {
+        (0..FIELD_BITS * times).fold(self.rem(modulus), |acc, _| (&acc * &acc).rem(modulus))
+    }
+}
+
+impl Add for &Poly {
+    type Output = Poly;
+
+    fn add(self, other: &Poly) -> Poly {
+        let len = self.coefficients.len().max(other.coefficients.len());
+        Poly::new(
+            (0..len)
+                .map(|i| {
+                    let a = self.coefficients.get(i).copied().unwrap_or_default();
+                    let b = other.coefficients.get(i).copied().unwrap_or_default();
+                    a + b
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Mul for &Poly {
+    type Output = Poly;
+
+    fn mul(self, other: &Poly) -> Poly {
+        if self.is_zero() || other.is_zero() {
+            return Poly::zero();
+        }
+        let mut product = vec![Gf128::ZERO; self.coefficients.len() + other.coefficients.len() - 1];
+        for (i, a) in self.coefficients.iter().enumerate() {
+            for (j, b) in other.coefficients.iter().enumerate() {
+                product[i + j] += *a * *b;
+            }
+        }
+        Poly::new(product)
+    }
+}
+
+/// Splits a monic polynomial into square-free factors with their multiplicities.
+pub fn square_free_factorization(f: &Poly) -> Vec<(Poly, usize)> {
+    let f = f.monic();
+    let mut factors = vec![];
+    let derivative = f.derivative();
+
+    if derivative.is_zero() {
+        // f is a perfect square
+        if f.degree().unwrap_or(0) > 0 {
+            for (factor, multiplicity) in square_free_factorization(&f.sqrt()) {
+                factors.push((factor, multiplicity * 2));
+            }
+        }
+        return factors;
+    }
+
+    let mut c = f.gcd(&derivative);
+    let mut w = f.div_rem(&c).0;
+    let mut multiplicity = 1;
+    while !w.is_one() {
+        let y = w.gcd(&c);
+        let factor = w.div_rem(&y).0;
+        if !factor.is_one() {
+            factors.push((factor.monic(), multiplicity));
+        }
+        w = y;
+        c = c.div_rem(&w).0;
+        multiplicity += 1;
+    }
+    // whatever is left only has factors with multiplicity divisible by 2
+    if !c.monic().is_one() {
+        for (factor, multiplicity) in square_free_factorization(&c.monic().sqrt()) {
+            factors.push((factor, multiplicity * 2));
+        }
+    }
+    factors
+}
+
+/// Splits a monic square-free polynomial into products of irreducible factors that
+/// all share the same degree, returned as `(product, degree)`.
+pub fn distinct_degree_factorization(f: &Poly) -> Vec<(Poly, usize)> {
+    let mut factors = vec![];
+    let mut remaining = f.monic();
+    let mut h = Poly::x().rem(&remaining);
+    let mut degree = 1;
+
+    while remaining.degree().unwrap_or(0) >= 2 * degree {
+        // x^(q^d) - x is the product of every irreducible of degree dividing d
+        h = h.frobenius(1, &remaining);
+        let g = remaining.gcd(&(&h + &Poly::x()));
+        if !g.is_one() {
+            remaining = remaining.div_rem(&g).0;
+            h = h.rem(&remaining);
+            factors.push((g, degree));
+        }
+        degree += 1;
+    }
+    if let Some(d) = remaining.degree().filter(|d| *d > 0) {
+        factors.push((remaining, d));
+    }
+    factors
+}
+
+/// Cantor–Zassenhaus splitting of a monic product of irreducibles of degree `degree`.
+pub fn equal_degree_factorization(f: &Poly, degree: usize) -> Vec<Poly> {
+    let f = f.monic();
+    let n = f.degree().unwrap_or(0);
+    if n <= degree {
+        return vec![f];
+    }
+
+    let mut factors = vec![f.clone()];
+    while factors.len() < n / degree {
+        // in characteristic 2 the trace map plays the role of a^((q^d - 1) / 2):
+        // it sends each irreducible factor's residue field onto {0, 1}
+        let a = Poly::random(n);
+        let mut trace = a.clone();
+        let mut power = a;
+        for _ in 1..FIELD_BITS * degree {
+            power = (&power * &power).rem(&f);
+            trace = &trace + &power;
+        }
+
+        factors = factors
+            .into_iter()
+            .flat_map(|u| {
+                if u.degree() == Some(degree) {
+                    return vec![u];
+                }
+                let g = u.gcd(&trace.rem(&u));
+                if g.is_one() || g == u {
+                    vec![u]
+                } else {
+                    let other = u.div_rem(&g).0.monic();
+                    vec![g, other]
+                }
+            })
+            .collect();
+    }
+    factors
+}
+
+/// Every distinct root of `f` in GF(2^128).
+pub fn roots(f: &Poly) -> Vec<Gf128> {
+    if f.degree().unwrap_or(0) == 0 {
+        return vec![];
+    }
+    square_free_factorization(f)
+        .iter()
+        .flat_map(|(factor, _)| distinct_degree_factorization(factor))
+        .filter(|(_, degree)| *degree == 1)
+        .flat_map(|(product, _)| equal_degree_factorization(&product, 1))
+        // a monic linear factor x + r has root r, as negation is the identity
+        .map(|linear| linear.coefficients()[0])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linear(root: u128) -> Poly {
+        Poly::new(vec![Gf128(root), Gf128::ONE])
+    }
+
+    #[test]
+    fn test_div_rem() {
+        let a = &linear(3) * &linear(5);
+        let (quotient, remainder) = (&a + &Poly::one()).div_rem(&linear(3));
+        assert_eq!(quotient, linear(5));
+        assert_eq!(remainder, Poly::one());
+    }
+
+    #[test]
+    fn test_square_free_factorization() {
+        let a = linear(3);
+        let b = linear(7);
+        let f = &(&(&a * &a) * &(&a * &b)) * &b;
+        let mut factors = square_free_factorization(&f);
+        factors.sort_by_key(|(_, multiplicity)| *multiplicity);
+        assert_eq!(factors, vec![(b, 2), (a, 3)]);
+    }
+
+    #[test]
+    fn test_roots() {
+        let expected = [11u128, 1 << 100, u128::MAX - 5];
+        // add a repeated root and an irreducible quadratic, x^2 + x + c with no roots
+        let irreducible = (1..)
+            .map(|c| Poly::new(vec![Gf128(c), Gf128::ONE, Gf128::ONE]))
+            .find(|p| distinct_degree_factorization(p) == vec![(p.clone(), 2)])
+            .unwrap();
+        let mut f = &irreducible * &linear(expected[0]);
+        for root in expected {
+            f = &f * &linear(root);
+        }
+
+        let mut found = roots(&f);
+        found.sort_by_key(|r| r.0);
+        let mut expected = expected.map(Gf128).to_vec();
+        expected.sort_by_key(|r| r.0);
+        assert_eq!(found, expected);
+    }
+}
@@ -1,4 +1,5 @@
 pub mod attacks;
+pub mod block_cipher;
 pub mod utils;
 pub mod pkcs7;
 pub mod cbc;
@@ -6,5 +7,16 @@ pub mod oracle;
 pub mod cookie;
 pub mod challenge_16;
 pub mod challenge_17;
+pub mod challenge_26;
+pub mod challenge_27;
 pub mod ctr;
+pub mod cfb;
+pub mod ofb;
+pub mod pcbc;
+pub mod xts;
+pub mod gf128;
+pub mod gf128_poly;
+pub mod gcm;
 pub mod mt_rng;
+pub mod rijndael;
+pub mod scoring;
@@ -1,17 +1,20 @@
-use std::str::from_utf8;
 
 use cryptopals::attacks::*;
 use cryptopals::cbc::cbc_decrypt;
 use cryptopals::cbc::cbc_encrypt;
 use cryptopals::challenge_16;
-use cryptopals::challenge_17;
 use cryptopals::challenge_17::Challenge17;
+use cryptopals::challenge_26::Challenge26;
+use cryptopals::challenge_27::Challenge27;
 use cryptopals::cookie::ProfileManager;
 use cryptopals::ctr::CTROracle;
 use cryptopals::mt_rng::rng;
+use cryptopals::oracle::AdminOracle;
+use cryptopals::oracle::ModeOracle;
 use cryptopals::oracle::Oracle;
 use cryptopals::oracle::StaticOracle;
 use cryptopals::pkcs7;
+use cryptopals::scoring::ChiSquared;
 use cryptopals::utils::*;
 use itertools::Itertools;
 use log::info;
@@ -19,7 +22,7 @@ use log::info;
 fn set1_challenge_3() {
     let input =
         Vec::<u8>::from_hex("1b37373331363f78151b7f2b783431333d78397828372d363c78373e783a393b3736");
-    let decrypted = attack_single_character_xor(input);
+    let decrypted = attack_single_character_xor(input, &ChiSquared::english());
 
     info!(
         "1.3 Resulting string: {}",
@@ -28,17 +31,15 @@ fn set1_challenge_3() {
 }
 
 fn set1_challenge_4() {
-    let input = std::fs::read_to_string("data/4.txt").expect("Unable to read file");
-
-    // read input line by line
-    let max = input.lines().fold(DecryptCipherResult::new(), |acc, line| {
-        let ciphertext = Vec::<u8>::from_hex(line);
-        acc.max(attack_single_character_xor(ciphertext))
-    });
+    let scorer = ChiSquared::english();
+    let candidates = detect_single_character_xor_in_file("data/4.txt", LineEncoding::Hex, 1, &scorer)
+        .expect("Unable to read file");
+    let best = &candidates[0];
 
     info!(
-        "1.4 Resulting string: {}",
-        std::str::from_utf8(&max.decoded).unwrap()
+        "1.4 Resulting string on line {}: {}",
+        best.line,
+        std::str::from_utf8(&best.plaintext).unwrap()
     );
 }
 
@@ -60,7 +61,8 @@ fn set1_challenge_6() {
     input = input.replace('\n', "");
 
     let ciphertext = Vec::<u8>::from_base64(&input);
-    let key = attack_repeating_key_xor(&ciphertext);
+    let key = attack_repeating_key_xor(&ciphertext, &ChiSquared::english())
+        .expect("Ciphertext too short to break");
     info!("1.6 key: {}", std::str::from_utf8(&key).unwrap());
 }
 
@@ -77,17 +79,18 @@ fn set1_challenge_7() {
 }
 
 fn set1_challenge_8() {
-    let duplicates = std::fs::read_to_string("data/8.txt")
-        .expect("Unable to read file")
-        .lines()
-        .map(Vec::<u8>::from_hex)
-        .filter(|ciphertext| ciphertext.as_slice().contains_duplicates(16))
-        .map(|ciphertext| ciphertext.to_hex())
-        .collect_vec();
-
-    let answer = duplicates.first().unwrap();
-
-    info!("1.8 ciphertexts with duplicate blocks: {:?}", duplicates);
+    let input = std::fs::read_to_string("data/8.txt").expect("Unable to read file");
+    for (i, line) in input.lines().enumerate() {
+        let report = ecb_report(&Vec::<u8>::from_hex(line), 16, 0);
+        if report.is_ecb(1e-6) {
+            info!(
+                "1.8 line {} repeats blocks {:?} (p = {:e} if random)",
+                i + 1,
+                report.repeats,
+                report.random_probability
+            );
+        }
+    }
 }
 
 fn set2_challenge_9() {
@@ -114,23 +117,16 @@ fn set2_challenge_10() {
 fn set2_challenge_11() {
     let input = "XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX";
     for _ in 0..100 {
-        let mut input = input.clone().as_bytes().to_vec();
+        let mut input = input.as_bytes().to_vec();
         input.extend_from_slice(&random_key(rand::random::<usize>() % 6 + 5));
         let mut random_string = random_key(rand::random::<usize>() % 6 + 5);
         random_string.extend_from_slice(&input);
 
-        let key = random_key(16);
-        let mode = if rand::random::<bool>() {
-            CipherMode::ECB
-        } else {
-            CipherMode::CBC
-        };
-        let cipher = match mode {
-            CipherMode::ECB => padded_encrypt_aes_128(&random_string, &key),
-            CipherMode::CBC => cbc_encrypt(&random_string, &key, &[0; 16]),
-        };
-
-        assert_eq!(detect_cbc_or_ecb(&cipher), mode);
+        let mode = CipherMode::ALL[rand::random::<usize>() % CipherMode::ALL.len()];
+        let cipher = ModeOracle::new(mode).encrypt(&random_string);
+
+        let detected = detect_cbc_or_ecb(&cipher);
+        assert_eq!(detected == CipherMode::ECB, mode == CipherMode::ECB);
     }
     info!("2.11 Success!");
 }
@@ -138,23 +134,20 @@ fn set2_challenge_11() {
 fn set2_challenge_12() {
     const MAGIC_STRING: &str = "Um9sbGluJyBpbiBteSA1LjAKV2l0aCBteSByYWctdG9wIGRvd24gc28gbXkgaGFpciBjYW4gYmxvdwpUaGUgZ2lybGllcyBvbiBzdGFuZGJ5IHdhdmluZyBqdXN0IHRvIHNheSBoaQpEaWQgeW91IHN0b3A/IE5vLCBJIGp1c3QgZHJvdmUgYnkK";
     let oracle = StaticOracle::new().with_suffix(&Vec::<u8>::from_base64(MAGIC_STRING)[..]);
-    let secret = attack_ecb(oracle);
+    let secret = attack_ecb(&oracle).expect("Unable to decrypt the secret");
     info!("{}", std::str::from_utf8(&secret).unwrap());
     assert!(std::str::from_utf8(&secret).unwrap().contains("Rollin"));
 }
 
 fn set1_challenge_13() {
     let profile_manager = ProfileManager::new();
-    let attack_block: Vec<u8> = pkcs7::pad_to_blocksize("admin".as_bytes().to_vec(), 16);
-    let standard_string = "foooo@bar.com";
-    let attack_string: String = ["foooo@bar.", from_utf8(&attack_block).unwrap(), ".com"].concat();
-    let admin_ciphertext = profile_manager.create_profile(&attack_string);
-    let normal_cipher = profile_manager.create_profile(standard_string);
-    let elevated_cookie = [&normal_cipher[..32], &admin_ciphertext[16..32]].concat();
-    // DO something to the cipher, making us an admin
-    let cookie = profile_manager.decrypt_profile(&elevated_cookie);
+    let elevated_cookie = ecb_cut_and_paste(&profile_manager, b"user", b"admin")
+        .expect("Unable to forge an admin profile");
+    let cookie = profile_manager
+        .decrypt_profile(&elevated_cookie)
+        .expect("Forged profile did not decrypt");
     assert!(ProfileManager::is_admin(&cookie));
-    info!("{:?}", cookie);
+    info!("{}", cookie);
 }
 
 fn set1_challenge_14() {
@@ -164,30 +157,24 @@ fn set1_challenge_14() {
         .with_prefix(PREFIX.as_bytes())
         .with_suffix(TARGET.as_bytes());
 
-    let secret = attack_ecb(oracle);
+    let secret = attack_ecb(&oracle).expect("Unable to decrypt the secret");
     info!("target: {}", std::str::from_utf8(&secret).unwrap());
     assert_eq!(&secret[0..TARGET.len()], TARGET.as_bytes());
+
+    let oracle = StaticOracle::new()
+        .with_random_prefix(64)
+        .with_suffix(TARGET.as_bytes());
+    let secret = attack_ecb_random_prefix(&oracle).expect("Unable to decrypt the secret");
+    assert_eq!(secret, TARGET.as_bytes());
 }
 
 fn set2_challenge_16() {
     let input = "A".repeat(16);
     let oracle = challenge_16::Challenge16::new();
-    let test = oracle.encrypt(&input.as_bytes());
+    let test = oracle.encrypt(input.as_bytes());
     assert!(!challenge_16::is_admin(&test));
 
-    let input_string = "A".repeat(16) + "AadminAtrueAAAAA"; // 32 bytes, we need to flip bytes 32, 38, 43
-                                                            // version 1
-    let mut ciphertext = challenge_16::encrypt(&input_string.as_bytes());
-
-    // set this character to
-    // by xoring its current value against the previous blocks cipher text
-    // and the desired value
-
-    //                 A         ;    old cipher result
-    ciphertext[32] = (0x41 ^ 0x3b) ^ ciphertext[32];
-    ciphertext[38] = (0x41 ^ 0x3d) ^ ciphertext[38];
-    ciphertext[43] = (0x41 ^ 0x3b) ^ ciphertext[43];
-
+    let ciphertext = cbc_bitflip_inject(&oracle, b";admin=true;").expect("Unable to forge ciphertext");
     assert!(challenge_16::is_admin(&ciphertext));
 }
 
@@ -195,8 +182,17 @@ fn set3_challenge_17() {
     let oracle = Challenge17::new();
     let (iv, cipher) = oracle.encrypt_random_input();
 
-    let result = oracle_padding_attack(&iv, &cipher, &oracle);
+    let result = oracle_padding_attack_stripped(&iv, &cipher, &oracle)
+        .expect("Padding oracle attack failed");
     info!("Challenge 17 result: {}", safe_string(&result));
+
+    let (_, stats) = parallel_padding_oracle_attack(&iv, &cipher, &oracle)
+        .expect("Padding oracle attack failed");
+    info!(
+        "Challenge 17 parallel attack used {} queries for {} bytes",
+        stats.total_queries(),
+        cipher.len()
+    );
 }
 
 fn set3_challenge_19() {
@@ -206,29 +202,46 @@ fn set3_challenge_19() {
 
     let encrypted = input
         .lines()
-        .map(|line| Vec::<u8>::from_base64(line))
+        .map(Vec::<u8>::from_base64)
         .map(|line| oracle.encrypt(&line))
         .collect_vec();
 
-    let mut decrypted = vec![String::new(); encrypted.len()];
-    let min_encrypted_len = encrypted.iter().map(|a| a.len()).min().unwrap();
-    for i in 0..min_encrypted_len {
-        let column = encrypted
-            .iter()
-            .filter_map(|line| line.get(i))
-            .map(|a| *a)
-            .collect_vec();
-
-        let result = attack_single_character_xor(column);
-        let column_decrypted = result.decoded;
-        for (j, val) in column_decrypted.iter().enumerate() {
-            decrypted[j].push(*val as char);
-        }
-    }
+    let mut broken = break_fixed_nonce_ctr(&encrypted, &ChiSquared::english());
+    // the tail of the longest line is only seen once, so patch it from the poem
+    let longest = encrypted.iter().position_max_by_key(|line| line.len()).unwrap();
+    broken.apply_hint(longest, 0, b"He, too, has been changed in his turn,");
 
+    let decrypted = broken.plaintexts().iter().map(|line| safe_string(line)).collect_vec();
     info!("Challenge 19 result: {:?}", decrypted);
 }
 
+fn set4_challenge_26() {
+    let oracle = Challenge26::new();
+    let forged = bitflip_inject(&oracle, b";admin=true;").expect("Unable to forge ciphertext");
+    assert!(oracle.is_admin(&forged));
+    info!("4.26 Success!");
+}
+
+fn set4_challenge_27() {
+    let oracle = Challenge27::new();
+    let key = recover_key_as_iv(&oracle).expect("Decryption did not leak the plaintext");
+    let forged = cbc_encrypt(b";admin=true;", &key, &key);
+    assert_eq!(oracle.is_admin(&forged), Ok(true));
+    info!("4.27 recovered key: {}", key.to_hex());
+}
+
+fn set4_challenge_25() {
+    let mut input = std::fs::read_to_string("data/7.txt").expect("Unable to read file");
+    input = input.replace('\n', "");
+    let plaintext = decrypt_aes_128_padded(&Vec::<u8>::from_base64(&input), b"YELLOW SUBMARINE");
+    let oracle = CTROracle::new(rand::random());
+    let ciphertext = oracle.encrypt(&plaintext);
+
+    let recovered = break_ctr_edit(&oracle, &ciphertext);
+    assert_eq!(recovered, plaintext);
+    info!("4.25 recovered: {}", safe_string(&recovered[..33]));
+}
+
 fn set3_challenge_21() {
     let current_time = std::time::SystemTime::now();
     let seed = current_time
@@ -297,4 +310,13 @@ fn main() {
 
     info!("Set 3 Challenge 21");
     set3_challenge_21();
+
+    info!("Set 4 Challenge 25");
+    set4_challenge_25();
+
+    info!("Set 4 Challenge 26");
+    set4_challenge_26();
+
+    info!("Set 4 Challenge 27");
+    set4_challenge_27();
 }
@@ -0,0 +1,56 @@
+use crate::{
+    block_cipher::{Aes128, BlockCipher},
+    utils::Xor,
+};
+
+/// OFB mode; encryption and decryption are the same operation.
+pub fn ofb(input: &[u8], key: &[u8], iv: &[u8]) -> Vec<u8> {
+    ofb_with(&Aes128::new(key), input, iv)
+}
+
+pub fn ofb_with<C: BlockCipher>(cipher: &C, input: &[u8], iv: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut keystream = iv.to_vec();
+
+    for block in input.chunks(C::BLOCK_SIZE) {
+        // the keystream only ever depends on the iv, never on the data
+        cipher.encrypt_block(&mut keystream);
+        let mut out = block.to_vec();
+        out.xor(&keystream);
+        output.extend_from_slice(&out);
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Hex;
+
+    #[test]
+    fn test_ofb_sp800_38a() {
+        // NIST SP 800-38A F.4.1
+        let key = Vec::<u8>::from_hex("2b7e151628aed2a6abf7158809cf4f3c");
+        let iv = Vec::<u8>::from_hex("000102030405060708090a0b0c0d0e0f");
+        let plain = Vec::<u8>::from_hex(
+            "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+             30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710",
+        );
+        let cipher = ofb(&plain, &key, &iv);
+        assert_eq!(
+            cipher.to_hex(),
+            "3b3fd92eb72dad20333449f8e83cfb4a7789508d16918f03f53c52dac54ed825\
+             9740051e9c5fecf64344f7a82260edcc304c6528f659c77866a510d9c1d6ae5e"
+        );
+        assert_eq!(ofb(&cipher, &key, &iv), plain);
+    }
+
+    #[test]
+    fn test_ofb_partial_block() {
+        let key = b"YELLOW SUBMARINE";
+        let iv = [0; 16];
+        let cipher = ofb(b"hello hello hello", key, &iv);
+        assert_eq!(cipher.len(), 17);
+        assert_eq!(ofb(&cipher, key, &iv), b"hello hello hello");
+    }
+}
@@ -1,31 +1,86 @@
-use std::str::from_utf8;
-use log::info;
+use crate::{
+    block_cipher::{padded_ecb_decrypt, padded_ecb_encrypt, Aes128, BlockCipher},
+    cbc::cbc_encrypt,
+    cfb::{cfb_encrypt, CfbSegment},
+    ctr::aes_ctr,
+    ofb::ofb,
+    pcbc::pcbc_encrypt,
+    pkcs7::{self, StripPaddingError},
+    utils::{random_key, CipherMode},
+    xts::xts_encrypt,
+};
+use std::time::{Duration, Instant};
 
-use crate::utils::{random_key, encrypt_aes_128, padded_encrypt_aes_128, Base64, Hex, decrypt_aes_128, decrypt_aes_128_padded};
-
-
-pub struct StaticOracle {
-    key: Vec<u8>,
+pub struct StaticOracle<C: BlockCipher = Aes128> {
+    cipher: C,
     suffix: Vec<u8>,
     prefix: Vec<u8>,
+    random_prefix: Option<usize>,
 }
 
 pub trait Oracle {
     fn encrypt(&self, input: &[u8]) -> Vec<u8>;
 }
 
+/// An oracle whose ciphertexts can be checked for an injected admin role.
+pub trait AdminOracle: Oracle {
+    fn is_admin(&self, ciphertext: &[u8]) -> bool;
+}
+
+/// Random-access re-encryption: replaces the plaintext under `ciphertext` at
+/// `offset` with `newtext` and returns the new ciphertext.
+pub trait EditOracle {
+    fn edit(&self, ciphertext: &[u8], offset: usize, newtext: &[u8]) -> Vec<u8>;
+}
+
+/// A CBC oracle that uses its key as the IV and, when it rejects a message,
+/// hands back the plaintext it decrypted to.
+pub trait KeyAsIvOracle: Oracle {
+    fn block_size(&self) -> usize;
+    fn rejected_plaintext(&self, ciphertext: &[u8]) -> Option<Vec<u8>>;
+}
+
+/// Reports whether a CBC ciphertext decrypts under `iv` to validly padded plaintext.
+pub trait PaddingOracle {
+    fn block_size(&self) -> usize;
+    fn is_valid_padding(&self, iv: &[u8], ciphertext: &[u8]) -> bool;
+}
+
+/// A CBC endpoint that answers every message the same way; only how long it
+/// takes to answer depends on the plaintext.
+pub trait TimingOracle {
+    fn block_size(&self) -> usize;
+    fn submit(&self, iv: &[u8], ciphertext: &[u8]);
+
+    /// How long `submit` takes to answer, by the wall clock unless the oracle
+    /// keeps time some other way.
+    fn response_time(&self, iv: &[u8], ciphertext: &[u8]) -> Duration {
+        let start = Instant::now();
+        self.submit(iv, ciphertext);
+        start.elapsed()
+    }
+}
 
 impl StaticOracle {
     pub fn new() -> Self {
-        let key = random_key(16);
-        //let key = Vec::<u8>::from_hex("65582b210e550f064039646021533269");
-        //let key = "0123456789abcdef".as_bytes().to_vec();
+        Self::with_key(&random_key(Aes128::KEY_SIZE))
+    }
+}
+
+impl Default for StaticOracle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: BlockCipher> StaticOracle<C> {
+    pub fn with_key(key: &[u8]) -> Self {
         Self {
-            key,
+            cipher: C::new(key),
             prefix: vec![],
             suffix: vec![],
+            random_prefix: None,
         }
-
     }
 
     pub fn with_suffix(mut self, suffix: &[u8]) -> Self {
@@ -38,22 +93,91 @@ impl StaticOracle {
         self
     }
 
+    /// Prepends a fresh random prefix of up to `max_length` bytes on every call,
+    /// ahead of any fixed prefix.
+    pub fn with_random_prefix(mut self, max_length: usize) -> Self {
+        self.random_prefix = Some(max_length);
+        self
+    }
+
     pub fn encrypt(&self, input: &[u8]) -> Vec<u8> {
-        let mut i = "".as_bytes().to_vec();
-        i.extend_from_slice(&self.prefix[..]);
+        let mut i = match self.random_prefix {
+            Some(max_length) => random_key(rand::random::<usize>() % (max_length + 1)),
+            None => vec![],
+        };
+        i.extend_from_slice(&self.prefix);
         i.extend_from_slice(input);
         i.extend_from_slice(&self.suffix[..]);
-        padded_encrypt_aes_128(&i, &self.key)
+        padded_ecb_encrypt(&self.cipher, &i)
     }
 
     pub fn decrypt(&self, ciphertext: &[u8]) -> Vec<u8> {
-        decrypt_aes_128_padded(ciphertext, &self.key)
+        self.try_decrypt(ciphertext).expect("Invalid padding")
+    }
+
+    pub fn try_decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>, StripPaddingError> {
+        padded_ecb_decrypt(&self.cipher, ciphertext)
     }
 }
 
-impl Oracle for StaticOracle {
+impl<C: BlockCipher> Oracle for StaticOracle<C> {
     fn encrypt(&self, input: &[u8]) -> Vec<u8> {
         self.encrypt(input)
     }
 }
 
+/// Encrypts under a fixed random key and IV using the configured mode.
+pub struct ModeOracle {
+    mode: CipherMode,
+    key: Vec<u8>,
+    iv: Vec<u8>,
+}
+
+impl ModeOracle {
+    pub fn new(mode: CipherMode) -> Self {
+        Self {
+            mode,
+            key: random_key(mode.key_size()),
+            iv: random_key(16),
+        }
+    }
+
+    pub fn mode(&self) -> CipherMode {
+        self.mode
+    }
+}
+
+impl Oracle for ModeOracle {
+    fn encrypt(&self, input: &[u8]) -> Vec<u8> {
+        let (key, iv) = (&self.key[..], &self.iv[..]);
+        match self.mode {
+            CipherMode::ECB => padded_ecb_encrypt(&Aes128::new(key), input),
+            CipherMode::CBC => cbc_encrypt(input, key, iv),
+            CipherMode::CTR => aes_ctr(input, key, &iv[..8]),
+            CipherMode::CFB1 => cfb_encrypt(input, key, iv, CfbSegment::Bit),
+            CipherMode::CFB8 => cfb_encrypt(input, key, iv, CfbSegment::Byte),
+            CipherMode::CFB128 => cfb_encrypt(input, key, iv, CfbSegment::Block),
+            CipherMode::OFB => ofb(input, key, iv),
+            CipherMode::PCBC => pcbc_encrypt(input, key, iv),
+            // a whole data unit is needed, so pad short inputs out like the block modes
+            CipherMode::XTS => xts_encrypt(&pkcs7::pad_to_blocksize(input.to_vec(), 16), key, 0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::detect_cbc_or_ecb;
+
+    #[test]
+    fn test_mode_oracle_detection() {
+        let input = [b'X'; 64];
+        for mode in CipherMode::ALL {
+            let oracle = ModeOracle::new(mode);
+            assert_eq!(oracle.mode(), mode);
+            let detected = detect_cbc_or_ecb(&oracle.encrypt(&input));
+            assert_eq!(detected == CipherMode::ECB, mode == CipherMode::ECB, "{:?}", mode);
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use crate::{
+    block_cipher::{Aes128, BlockCipher},
+    pkcs7,
+    utils::Xor,
+};
+
+pub fn pcbc_encrypt(input: &[u8], key: &[u8], iv: &[u8]) -> Vec<u8> {
+    pcbc_encrypt_with(&Aes128::new(key), input, iv)
+}
+
+pub fn pcbc_decrypt(cipher: Vec<u8>, key: &[u8], iv: &[u8]) -> Vec<u8> {
+    pcbc_decrypt_with(&Aes128::new(key), cipher, iv)
+}
+
+/// Like CBC, but each block is also chained with the previous plaintext.
+pub fn pcbc_encrypt_with<C: BlockCipher>(cipher: &C, input: &[u8], iv: &[u8]) -> Vec<u8> {
+    let padded = pkcs7::pad_to_blocksize(input.to_vec(), C::BLOCK_SIZE);
+    let mut chain = iv.to_vec();
+
+    let mut output = Vec::new();
+    for block in padded.chunks(C::BLOCK_SIZE) {
+        let mut encrypted = block.to_vec();
+        encrypted.xor(&chain);
+        cipher.encrypt_block(&mut encrypted);
+        output.extend_from_slice(&encrypted);
+        // the next block is chained with P_i xor C_i
+        encrypted.xor(&block.to_vec());
+        chain = encrypted;
+    }
+    output
+}
+
+pub fn pcbc_decrypt_with<C: BlockCipher>(cipher: &C, input: Vec<u8>, iv: &[u8]) -> Vec<u8> {
+    let mut chain = iv.to_vec();
+
+    let mut output = Vec::new();
+    for block in input.chunks(C::BLOCK_SIZE) {
+        let mut decrypted = block.to_vec();
+        cipher.decrypt_block(&mut decrypted);
+        decrypted.xor(&chain);
+        output.extend_from_slice(&decrypted);
+        decrypted.xor(&block.to_vec());
+        chain = decrypted;
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pcbc_round_trip() {
+        let key = b"YELLOW SUBMARINE";
+        let iv = [7; 16];
+        let input = b"Burning 'em, if you ain't quick and nimble";
+        let cipher = pcbc_encrypt(input, key, &iv);
+        assert_eq!(cipher.len(), 48);
+        let plain = pcbc_decrypt(cipher, key, &iv);
+        assert_eq!(pkcs7::strip_padding(plain).unwrap(), input);
+    }
+
+    #[test]
+    fn test_pcbc_error_propagates() {
+        // unlike CBC, a corrupted block garbles every block after it
+        let key = b"YELLOW SUBMARINE";
+        let iv = [7; 16];
+        let input = [b'A'; 64];
+        let mut cipher = pcbc_encrypt(&input, key, &iv);
+        cipher[0] ^= 1;
+        let plain = pcbc_decrypt(cipher, key, &iv);
+        assert!(plain[16..64].chunks(16).all(|block| block != [b'A'; 16]));
+    }
+}
@@ -1,6 +1,3 @@
-use crate::utils::Hex;
-
-
 pub fn pad_to_blocksize(mut bytes: Vec<u8>, blocksize: usize) -> Vec<u8> {
     let mut padding_needed = blocksize - (bytes.len() % blocksize);
     if padding_needed == 0 {
@@ -16,12 +13,12 @@ pub enum StripPaddingError {
 }
 
 pub fn strip_padding(mut bytes: Vec<u8>) -> Result<Vec<u8>, StripPaddingError> {
-    let padding = bytes.pop().unwrap();
-    if padding == 0 {
+    let padding = bytes.pop().ok_or(StripPaddingError::InvalidPadding)?;
+    if padding == 0 || padding as usize > bytes.len() + 1 {
         return Err(StripPaddingError::InvalidPadding);
     }
     // make sure all the last $padding bytes are equal to $padding
-    for i in 0..padding.checked_sub(1).unwrap_or(0) {
+    for i in 0..padding.saturating_sub(1) {
         if bytes[bytes.len() - 1 - i as usize] != padding {
             return Err(StripPaddingError::InvalidPadding);
         }
@@ -34,6 +31,7 @@ pub fn strip_padding(mut bytes: Vec<u8>) -> Result<Vec<u8>, StripPaddingError> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::Hex;
 
     #[test]
     fn test_pad_to_blocksize() {
@@ -65,4 +63,11 @@ mod tests {
         let stripped = strip_padding(input);
         assert_eq!(stripped, Err(StripPaddingError::InvalidPadding));
     }
+
+    #[test]
+    fn test_padding_strip_fails_when_longer_than_input() {
+        let input = Vec::<u8>::from_hex("F1F1F1F1F1F1F1F1F1F1F1F1F1F1F1F1");
+        assert_eq!(strip_padding(input), Err(StripPaddingError::InvalidPadding));
+        assert_eq!(strip_padding(vec![]), Err(StripPaddingError::InvalidPadding));
+    }
 }
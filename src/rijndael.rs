@@ -0,0 +1,488 @@
+//! A from-scratch AES (FIPS-197) with every round step exposed.
+//!
+//! Two S-box implementations are available: the classic lookup table, whose
+//! secret-dependent memory accesses leak through the cache, and a table-free one
+//! that computes the GF(2^8) inverse with branch-free arithmetic.
+//!
+//! The state is kept in the FIPS-197 byte order: byte `r + 4 * c` of a block is row
+//! `r` of column `c`, so a block can be used as a state without any transposition.
+
+use crate::block_cipher::BlockCipher;
+
+pub type State = [u8; 16];
+
+pub const SBOX: [u8; 256] = build_sbox();
+pub const INV_SBOX: [u8; 256] = build_inv_sbox();
+
+const RCON: [u8; 10] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36];
+
+/// Multiplies by `x` in GF(2^8) modulo the AES polynomial.
+pub const fn xtime(a: u8) -> u8 {
+    (a << 1) ^ (((a >> 7) & 1) * 0x1b)
+}
+
+/// Multiplication in GF(2^8) that branches on `b`; only use it with public `b`.
+pub const fn gmul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 == 1 {
+            product ^= a;
+        }
+        a = xtime(a);
+        b >>= 1;
+    }
+    product
+}
+
+/// Multiplication in GF(2^8) with no secret-dependent branches or memory accesses.
+pub fn ct_gmul(mut a: u8, b: u8) -> u8 {
+    let mut product = 0;
+    for i in 0..8 {
+        product ^= a & 0u8.wrapping_sub((b >> i) & 1);
+        a = xtime(a);
+    }
+    product
+}
+
+/// Computes the S-box without a table: the inverse a^254 followed by the affine map.
+pub fn ct_sbox(a: u8) -> u8 {
+    let inverse = ct_inverse(a);
+    inverse
+        ^ inverse.rotate_left(1)
+        ^ inverse.rotate_left(2)
+        ^ inverse.rotate_left(3)
+        ^ inverse.rotate_left(4)
+        ^ 0x63
+}
+
+pub fn ct_inv_sbox(s: u8) -> u8 {
+    ct_inverse(s.rotate_left(1) ^ s.rotate_left(3) ^ s.rotate_left(6) ^ 0x05)
+}
+
+fn ct_inverse(a: u8) -> u8 {
+    // a^254 = a^2 * a^4 * ... * a^128
+    let mut square = ct_gmul(a, a);
+    let mut inverse = square;
+    for _ in 0..6 {
+        square = ct_gmul(square, square);
+        inverse = ct_gmul(inverse, square);
+    }
+    inverse
+}
+
+const fn build_sbox() -> [u8; 256] {
+    let mut sbox = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        // the multiplicative inverse is a^254, and 0 maps to 0
+        let a = i as u8;
+        let mut inverse = 1;
+        let mut j = 0;
+        while j < 254 {
+            inverse = gmul(inverse, a);
+            j += 1;
+        }
+        if a == 0 {
+            inverse = 0;
+        }
+        sbox[i] = inverse
+            ^ inverse.rotate_left(1)
+            ^ inverse.rotate_left(2)
+            ^ inverse.rotate_left(3)
+            ^ inverse.rotate_left(4)
+            ^ 0x63;
+        i += 1;
+    }
+    sbox
+}
+
+const fn build_inv_sbox() -> [u8; 256] {
+    let sbox = build_sbox();
+    let mut inv = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        inv[sbox[i] as usize] = i as u8;
+        i += 1;
+    }
+    inv
+}
+
+pub fn sub_bytes(state: &mut State) {
+    state.iter_mut().for_each(|b| *b = SBOX[*b as usize]);
+}
+
+pub fn inv_sub_bytes(state: &mut State) {
+    state.iter_mut().for_each(|b| *b = INV_SBOX[*b as usize]);
+}
+
+pub fn ct_sub_bytes(state: &mut State) {
+    state.iter_mut().for_each(|b| *b = ct_sbox(*b));
+}
+
+pub fn ct_inv_sub_bytes(state: &mut State) {
+    state.iter_mut().for_each(|b| *b = ct_inv_sbox(*b));
+}
+
+/// Rotates row `r` left by `r` positions.
+pub fn shift_rows(state: &mut State) {
+    let old = *state;
+    for r in 1..4 {
+        for c in 0..4 {
+            state[r + 4 * c] = old[r + 4 * ((c + r) % 4)];
+        }
+    }
+}
+
+pub fn inv_shift_rows(state: &mut State) {
+    let old = *state;
+    for r in 1..4 {
+        for c in 0..4 {
+            state[r + 4 * ((c + r) % 4)] = old[r + 4 * c];
+        }
+    }
+}
+
+// multiplies a column by the circulant matrix [02 03 01 01]
+fn mix_column(column: &mut [u8]) {
+    let a = [column[0], column[1], column[2], column[3]];
+    let all = a[0] ^ a[1] ^ a[2] ^ a[3];
+    for (r, out) in column.iter_mut().enumerate() {
+        *out = a[r] ^ all ^ xtime(a[r] ^ a[(r + 1) % 4]);
+    }
+}
+
+pub fn mix_columns(state: &mut State) {
+    state.chunks_mut(4).for_each(mix_column);
+}
+
+pub fn inv_mix_columns(state: &mut State) {
+    // [0e 0b 0d 09] factors as [02 03 01 01] * [05 00 04 00]
+    state.chunks_mut(4).for_each(|column| {
+        let u = xtime(xtime(column[0] ^ column[2]));
+        let v = xtime(xtime(column[1] ^ column[3]));
+        column[0] ^= u;
+        column[1] ^= v;
+        column[2] ^= u;
+        column[3] ^= v;
+        mix_column(column);
+    });
+}
+
+pub fn add_round_key(state: &mut State, round_key: &State) {
+    state
+        .iter_mut()
+        .zip(round_key.iter())
+        .for_each(|(s, k)| *s ^= k);
+}
+
+/// Expands a 16, 24 or 32 byte key into `rounds + 1` round keys.
+pub fn expand_key(key: &[u8]) -> Vec<State> {
+    expand_key_with(key, |b| SBOX[b as usize])
+}
+
+/// Key expansion using the table-free S-box.
+pub fn ct_expand_key(key: &[u8]) -> Vec<State> {
+    expand_key_with(key, ct_sbox)
+}
+
+fn expand_key_with(key: &[u8], sbox: fn(u8) -> u8) -> Vec<State> {
+    let nk = key.len() / 4;
+    let rounds = match key.len() {
+        16 => 10,
+        24 => 12,
+        32 => 14,
+        len => panic!("Invalid AES key length: {}", len),
+    };
+
+    let mut words: Vec<[u8; 4]> = key
+        .chunks(4)
+        .map(|w| [w[0], w[1], w[2], w[3]])
+        .collect();
+    for i in nk..4 * (rounds + 1) {
+        let mut temp = words[i - 1];
+        if i % nk == 0 {
+            temp.rotate_left(1);
+            temp.iter_mut().for_each(|b| *b = sbox(*b));
+            temp[0] ^= RCON[i / nk - 1];
+        } else if nk > 6 && i % nk == 4 {
+            temp.iter_mut().for_each(|b| *b = sbox(*b));
+        }
+        let previous = words[i - nk];
+        words.push([
+            previous[0] ^ temp[0],
+            previous[1] ^ temp[1],
+            previous[2] ^ temp[2],
+            previous[3] ^ temp[3],
+        ]);
+    }
+
+    words
+        .chunks(4)
+        .map(|round| {
+            let mut round_key = [0; 16];
+            round_key.copy_from_slice(&round.concat());
+            round_key
+        })
+        .collect()
+}
+
+pub struct Rijndael {
+    round_keys: Vec<State>,
+    constant_time: bool,
+}
+
+impl Rijndael {
+    /// AES using lookup tables for the S-box.
+    pub fn new(key: &[u8]) -> Self {
+        Rijndael {
+            round_keys: expand_key(key),
+            constant_time: false,
+        }
+    }
+
+    /// AES with no secret-dependent table lookups or branches.
+    pub fn new_constant_time(key: &[u8]) -> Self {
+        Rijndael {
+            round_keys: ct_expand_key(key),
+            constant_time: true,
+        }
+    }
+
+    pub fn is_constant_time(&self) -> bool {
+        self.constant_time
+    }
+
+    fn sub_bytes(&self, state: &mut State) {
+        if self.constant_time {
+            ct_sub_bytes(state)
+        } else {
+            sub_bytes(state)
+        }
+    }
+
+    fn inv_sub_bytes(&self, state: &mut State) {
+        if self.constant_time {
+            ct_inv_sub_bytes(state)
+        } else {
+            inv_sub_bytes(state)
+        }
+    }
+
+    /// Number of rounds of the full cipher (10, 12 or 14).
+    pub fn rounds(&self) -> usize {
+        self.round_keys.len() - 1
+    }
+
+    pub fn round_keys(&self) -> &[State] {
+        &self.round_keys
+    }
+
+    /// Encrypts with only the first `rounds` rounds of the key schedule.
+    ///
+    /// As in the full cipher the last round omits MixColumns, so a reduced-round
+    /// variant is exactly what the square/integral literature calls "r-round AES".
+    pub fn encrypt_rounds(&self, block: &mut State, rounds: usize) {
+        assert!(rounds >= 1 && rounds <= self.rounds(), "Invalid round count");
+        add_round_key(block, &self.round_keys[0]);
+        for round in 1..=rounds {
+            self.sub_bytes(block);
+            shift_rows(block);
+            if round != rounds {
+                mix_columns(block);
+            }
+            add_round_key(block, &self.round_keys[round]);
+        }
+    }
+
+    /// Inverse of [`Rijndael::encrypt_rounds`] for the same round count.
+    pub fn decrypt_rounds(&self, block: &mut State, rounds: usize) {
+        assert!(rounds >= 1 && rounds <= self.rounds(), "Invalid round count");
+        for round in (1..=rounds).rev() {
+            add_round_key(block, &self.round_keys[round]);
+            if round != rounds {
+                inv_mix_columns(block);
+            }
+            inv_shift_rows(block);
+            self.inv_sub_bytes(block);
+        }
+        add_round_key(block, &self.round_keys[0]);
+    }
+
+    /// Returns the state after the initial key whitening and after each full round.
+    ///
+    /// Entry `i` is the state at the end of round `i`, so `trace(b)[rounds()]` is the
+    /// ciphertext.
+    pub fn trace(&self, block: &State) -> Vec<State> {
+        let mut state = *block;
+        add_round_key(&mut state, &self.round_keys[0]);
+        let mut states = vec![state];
+        for round in 1..=self.rounds() {
+            self.sub_bytes(&mut state);
+            shift_rows(&mut state);
+            if round != self.rounds() {
+                mix_columns(&mut state);
+            }
+            add_round_key(&mut state, &self.round_keys[round]);
+            states.push(state);
+        }
+        states
+    }
+}
+
+impl BlockCipher for Rijndael {
+    const BLOCK_SIZE: usize = 16;
+
+    fn new(key: &[u8]) -> Self {
+        Rijndael::new(key)
+    }
+
+    fn encrypt_block(&self, block: &mut [u8]) {
+        let state: &mut State = block.try_into().expect("Invalid block size");
+        self.encrypt_rounds(state, self.rounds());
+    }
+
+    fn decrypt_block(&self, block: &mut [u8]) {
+        let state: &mut State = block.try_into().expect("Invalid block size");
+        self.decrypt_rounds(state, self.rounds());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{block_cipher::ecb_encrypt, utils::{encrypt_aes_128, Hex}};
+
+    fn state(hex: &str) -> State {
+        Vec::<u8>::from_hex(hex).try_into().unwrap()
+    }
+
+    fn check_vector(key: &str, expected: &str) {
+        let cipher = Rijndael::new(&Vec::<u8>::from_hex(key));
+        let mut block = state("00112233445566778899aabbccddeeff");
+        cipher.encrypt_block(&mut block);
+        assert_eq!(block.to_vec().to_hex(), expected);
+        cipher.decrypt_block(&mut block);
+        assert_eq!(block.to_vec().to_hex(), "00112233445566778899aabbccddeeff");
+    }
+
+    #[test]
+    fn test_sbox() {
+        assert_eq!(SBOX[0x00], 0x63);
+        assert_eq!(SBOX[0x53], 0xed);
+        assert_eq!(INV_SBOX[0xed], 0x53);
+    }
+
+    #[test]
+    fn test_ct_sbox_matches_table() {
+        for a in 0..=255u8 {
+            assert_eq!(ct_sbox(a), SBOX[a as usize]);
+            assert_eq!(ct_inv_sbox(a), INV_SBOX[a as usize]);
+        }
+    }
+
+    #[test]
+    fn test_constant_time_matches_table() {
+        for key_len in [16, 24, 32] {
+            let key = (0..key_len as u8).collect::<Vec<_>>();
+            let table = Rijndael::new(&key);
+            let constant_time = Rijndael::new_constant_time(&key);
+            assert_eq!(table.round_keys(), constant_time.round_keys());
+
+            let mut a = *b"0123456789abcdef";
+            let mut b = a;
+            table.encrypt_block(&mut a);
+            constant_time.encrypt_block(&mut b);
+            assert_eq!(a, b);
+            constant_time.decrypt_block(&mut b);
+            assert_eq!(&b, b"0123456789abcdef");
+        }
+    }
+
+    #[test]
+    fn test_key_expansion() {
+        // FIPS-197 appendix A.1
+        let round_keys = expand_key(&Vec::<u8>::from_hex("2b7e151628aed2a6abf7158809cf4f3c"));
+        assert_eq!(round_keys.len(), 11);
+        assert_eq!(
+            round_keys[10].to_vec().to_hex(),
+            "d014f9a8c9ee2589e13f0cc8b6630ca6"
+        );
+    }
+
+    #[test]
+    fn test_fips_197_vectors() {
+        check_vector(
+            "000102030405060708090a0b0c0d0e0f",
+            "69c4e0d86a7b0430d8cdb78070b4c55a",
+        );
+        check_vector(
+            "000102030405060708090a0b0c0d0e0f1011121314151617",
+            "dda97ca4864cdfe06eaf70a0ec0d7191",
+        );
+        check_vector(
+            "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+            "8ea2b7ca516745bfeafc49904b496089",
+        );
+    }
+
+    #[test]
+    fn test_trace_matches_fips_197() {
+        let cipher = Rijndael::new(&Vec::<u8>::from_hex("000102030405060708090a0b0c0d0e0f"));
+        let states = cipher.trace(&state("00112233445566778899aabbccddeeff"));
+        // round[1].start and round[2].start in appendix C.1
+        assert_eq!(states[0].to_vec().to_hex(), "00102030405060708090a0b0c0d0e0f0");
+        assert_eq!(states[1].to_vec().to_hex(), "89d810e8855ace682d1843d8cb128fe4");
+        assert_eq!(states[10].to_vec().to_hex(), "69c4e0d86a7b0430d8cdb78070b4c55a");
+    }
+
+    #[test]
+    fn test_matches_utils_encrypt_aes_128() {
+        let key = b"YELLOW SUBMARINE";
+        let input = b"Burning 'em, if you ain't quick and nimble I go crazy";
+        let padded = crate::pkcs7::pad_to_blocksize(input.to_vec(), 16);
+        assert_eq!(
+            ecb_encrypt(&Rijndael::new(key), &padded),
+            encrypt_aes_128(&padded, key)
+        );
+    }
+
+    #[test]
+    fn test_reduced_round_round_trip() {
+        let cipher = Rijndael::new(b"YELLOW SUBMARINE");
+        for rounds in 1..=10 {
+            let mut block = *b"0123456789abcdef";
+            cipher.encrypt_rounds(&mut block, rounds);
+            cipher.decrypt_rounds(&mut block, rounds);
+            assert_eq!(&block, b"0123456789abcdef");
+        }
+    }
+
+    #[test]
+    fn test_integral_property_four_rounds() {
+        // a Λ-set: one active byte taking every value, the rest constant
+        let cipher = Rijndael::new(b"YELLOW SUBMARINE");
+        let lambda_set = (0..=255u8).map(|i| {
+            let mut block = [0x42; 16];
+            block[0] = i;
+            block
+        });
+
+        let mut balanced = [0u8; 16];
+        let mut partial_sum = 0u8;
+        let last_key = cipher.round_keys()[4];
+        for block in lambda_set {
+            let trace = cipher.trace(&block);
+            balanced
+                .iter_mut()
+                .zip(trace[3].iter())
+                .for_each(|(acc, b)| *acc ^= b);
+
+            // peel one byte of the last round off a 4-round encryption with the right key guess
+            let mut encrypted = block;
+            cipher.encrypt_rounds(&mut encrypted, 4);
+            partial_sum ^= INV_SBOX[(encrypted[0] ^ last_key[0]) as usize];
+        }
+        assert_eq!(balanced, [0; 16]);
+        assert_eq!(partial_sum, 0);
+    }
+}
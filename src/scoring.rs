@@ -0,0 +1,261 @@
+use std::{io, path::Path};
+
+use crate::attacks::score_character;
+
+/// Rates how much a candidate plaintext looks like the language we expect.
+/// Higher scores are more likely; only the ordering is meaningful.
+pub trait Scorer {
+    fn score(&self, plaintext: &[u8]) -> f64;
+}
+
+/// The original hand-written table from [`score_character`], which only knows
+/// lowercase letters and spaces.
+pub struct CharacterTable;
+
+impl Scorer for CharacterTable {
+    fn score(&self, plaintext: &[u8]) -> f64 {
+        plaintext
+            .iter()
+            .map(|byte| score_character(&(*byte as char)) as f64)
+            .sum()
+    }
+}
+
+/// Lowercases the plaintext before handing it to another scorer.
+pub struct CaseInsensitive<S: Scorer>(pub S);
+
+impl<S: Scorer> Scorer for CaseInsensitive<S> {
+    fn score(&self, plaintext: &[u8]) -> f64 {
+        self.0.score(&plaintext.to_ascii_lowercase())
+    }
+}
+
+/// Subtracts `penalty` from another scorer for every byte that isn't printable
+/// ASCII or ordinary whitespace.
+pub struct PrintablePenalty<S: Scorer> {
+    inner: S,
+    penalty: f64,
+}
+
+impl<S: Scorer> PrintablePenalty<S> {
+    pub fn new(inner: S, penalty: f64) -> Self {
+        Self { inner, penalty }
+    }
+}
+
+impl<S: Scorer> Scorer for PrintablePenalty<S> {
+    fn score(&self, plaintext: &[u8]) -> f64 {
+        let unprintable = plaintext
+            .iter()
+            .filter(|&&byte| !matches!(byte, b'\n' | b'\r' | b'\t' | 0x20..=0x7e))
+            .count();
+        self.inner.score(plaintext) - self.penalty * unprintable as f64
+    }
+}
+
+// a-z, then space, then everything else
+const UNIGRAMS: usize = 28;
+const SPACE: usize = 26;
+const OTHER: usize = 27;
+
+/// Relative frequencies of letters a-z in English text.
+const ENGLISH_LETTERS: [f64; 26] = [
+    8.167, 1.492, 2.782, 4.253, 12.702, 2.228, 2.015, 6.094, 6.966, 0.153, 0.772, 4.025, 2.406,
+    6.749, 7.507, 1.929, 0.095, 5.987, 6.327, 9.056, 2.758, 0.978, 2.360, 0.150, 1.974, 0.074,
+];
+
+/// Percentages of the most common English bigrams; the rest share what's left.
+const ENGLISH_BIGRAMS: [(&[u8; 2], f64); 40] = [
+    (b"th", 3.56), (b"he", 3.07), (b"in", 2.43), (b"er", 2.05), (b"an", 1.99),
+    (b"re", 1.85), (b"on", 1.76), (b"at", 1.49), (b"en", 1.45), (b"nd", 1.35),
+    (b"ti", 1.34), (b"es", 1.34), (b"or", 1.28), (b"te", 1.20), (b"of", 1.17),
+    (b"ed", 1.17), (b"is", 1.13), (b"it", 1.12), (b"al", 1.09), (b"ar", 1.07),
+    (b"st", 1.05), (b"to", 1.04), (b"nt", 1.04), (b"ng", 0.95), (b"se", 0.93),
+    (b"ha", 0.93), (b"as", 0.87), (b"ou", 0.87), (b"io", 0.83), (b"le", 0.83),
+    (b"ve", 0.83), (b"co", 0.79), (b"me", 0.79), (b"de", 0.76), (b"hi", 0.76),
+    (b"ri", 0.73), (b"ro", 0.73), (b"ic", 0.70), (b"ne", 0.69), (b"ea", 0.69),
+];
+
+fn unigram_index(byte: u8) -> usize {
+    match byte.to_ascii_lowercase() {
+        c @ b'a'..=b'z' => (c - b'a') as usize,
+        b' ' => SPACE,
+        _ => OTHER,
+    }
+}
+
+fn letter_index(byte: u8) -> Option<usize> {
+    let c = byte.to_ascii_lowercase();
+    c.is_ascii_lowercase().then(|| (c - b'a') as usize)
+}
+
+/// Expected case-insensitive letter and bigram probabilities for a language.
+#[derive(Clone, Debug)]
+pub struct FrequencyModel {
+    unigrams: [f64; UNIGRAMS],
+    bigrams: [[f64; 26]; 26],
+}
+
+impl FrequencyModel {
+    pub fn english() -> Self {
+        // letters make up about 80% of prose and spaces most of the rest
+        let mut unigrams = [0.; UNIGRAMS];
+        for (i, frequency) in ENGLISH_LETTERS.iter().enumerate() {
+            unigrams[i] = frequency / 100. * 0.8;
+        }
+        unigrams[SPACE] = 0.18;
+        unigrams[OTHER] = 0.02;
+
+        let listed = ENGLISH_BIGRAMS.iter().map(|(_, f)| f).sum::<f64>();
+        let rest = (100. - listed) / (26. * 26. - ENGLISH_BIGRAMS.len() as f64) / 100.;
+        let mut bigrams = [[rest; 26]; 26];
+        for (pair, frequency) in ENGLISH_BIGRAMS {
+            bigrams[(pair[0] - b'a') as usize][(pair[1] - b'a') as usize] = frequency / 100.;
+        }
+        FrequencyModel { unigrams, bigrams }
+    }
+
+    /// Counts frequencies in a sample of text, add-one smoothed so nothing in the
+    /// model is impossible.
+    pub fn train(corpus: &[u8]) -> Self {
+        let mut unigrams = [1.; UNIGRAMS];
+        let mut bigrams = [[1.; 26]; 26];
+        for &byte in corpus {
+            unigrams[unigram_index(byte)] += 1.;
+        }
+        for pair in corpus.windows(2) {
+            if let (Some(a), Some(b)) = (letter_index(pair[0]), letter_index(pair[1])) {
+                bigrams[a][b] += 1.;
+            }
+        }
+
+        let total = unigrams.iter().sum::<f64>();
+        unigrams.iter_mut().for_each(|p| *p /= total);
+        let total = bigrams.iter().flatten().sum::<f64>();
+        bigrams.iter_mut().flatten().for_each(|p| *p /= total);
+        FrequencyModel { unigrams, bigrams }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::train(&std::fs::read(path)?))
+    }
+}
+
+/// Pearson's chi-squared statistic of `observed` counts against `expected`
+/// probabilities.
+fn chi_squared(observed: &[f64], expected: &[f64]) -> f64 {
+    let total = observed.iter().sum::<f64>();
+    if total == 0. {
+        return 0.;
+    }
+    observed
+        .iter()
+        .zip(expected)
+        .map(|(o, p)| {
+            let e = p * total;
+            (o - e) * (o - e) / e
+        })
+        .sum()
+}
+
+/// Chi-squared goodness of fit against a [`FrequencyModel`], counting letters
+/// case-insensitively. Bigrams only make sense for contiguous text, so they are
+/// weighted separately and can be turned off for transposed columns.
+pub struct ChiSquared {
+    model: FrequencyModel,
+    bigram_weight: f64,
+}
+
+impl ChiSquared {
+    pub fn new(model: FrequencyModel) -> Self {
+        ChiSquared {
+            model,
+            bigram_weight: 0.,
+        }
+    }
+
+    pub fn english() -> Self {
+        Self::new(FrequencyModel::english())
+    }
+
+    pub fn with_bigram_weight(mut self, bigram_weight: f64) -> Self {
+        self.bigram_weight = bigram_weight;
+        self
+    }
+}
+
+impl Scorer for ChiSquared {
+    fn score(&self, plaintext: &[u8]) -> f64 {
+        let mut unigrams = [0.; UNIGRAMS];
+        for &byte in plaintext {
+            unigrams[unigram_index(byte)] += 1.;
+        }
+        let mut statistic = chi_squared(&unigrams, &self.model.unigrams);
+
+        if self.bigram_weight > 0. {
+            let mut bigrams = [[0.; 26]; 26];
+            for pair in plaintext.windows(2) {
+                if let (Some(a), Some(b)) = (letter_index(pair[0]), letter_index(pair[1])) {
+                    bigrams[a][b] += 1.;
+                }
+            }
+            statistic += self.bigram_weight
+                * chi_squared(bigrams.as_flattened(), self.model.bigrams.as_flattened());
+        }
+        -statistic
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Xor;
+
+    const ENGLISH: &[u8] =
+        b"Now that the party is jumping, with the bass kicked in and the Vega's are pumpin'";
+
+    /// English should beat the same text under a wrong key, including the key
+    /// that only flips case and turns spaces into nulls.
+    fn prefers_english(scorer: &impl Scorer) -> bool {
+        [0x55, 0x20].iter().all(|key| {
+            let mut wrong = ENGLISH.to_vec();
+            wrong.xor(key);
+            scorer.score(ENGLISH) > scorer.score(&wrong)
+        })
+    }
+
+    #[test]
+    fn test_chi_squared_prefers_english() {
+        assert!(prefers_english(&ChiSquared::english()));
+        assert!(prefers_english(&ChiSquared::english().with_bigram_weight(0.5)));
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        assert_eq!(CharacterTable.score(b"ETAOIN"), 0.);
+        assert_eq!(
+            CaseInsensitive(CharacterTable).score(b"ETAOIN"),
+            CharacterTable.score(b"etaoin")
+        );
+    }
+
+    #[test]
+    fn test_printable_penalty() {
+        let scorer = PrintablePenalty::new(CharacterTable, 50.);
+        assert_eq!(scorer.score(b"a\n"), CharacterTable.score(b"a\n"));
+        assert_eq!(scorer.score(b"a\x00\xff"), CharacterTable.score(b"a") - 100.);
+        assert!(prefers_english(&PrintablePenalty::new(CaseInsensitive(CharacterTable), 20.)));
+    }
+
+    #[test]
+    fn test_trained_model() {
+        let path = std::env::temp_dir().join(format!("cryptopals-corpus-{}", std::process::id()));
+        std::fs::write(&path, ENGLISH.repeat(4)).unwrap();
+        let model = FrequencyModel::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let scorer = ChiSquared::new(model).with_bigram_weight(1.);
+        assert!(prefers_english(&scorer));
+        assert!(FrequencyModel::from_file("data/does-not-exist").is_err());
+    }
+}
@@ -1,20 +1,44 @@
-use std::str::from_utf8;
-
 use base64::{engine::general_purpose, Engine as _};
 use itertools::Itertools;
-use aes::Aes128;
-use aes::cipher::{
-    BlockEncrypt, BlockDecrypt, KeyInit,
-    generic_array::GenericArray,
-};
-use log::info;
+use std::collections::HashMap;
 
-use crate::pkcs7;
+use crate::block_cipher::{
+    ecb_decrypt, ecb_encrypt, padded_ecb_decrypt, padded_ecb_encrypt, Aes128, BlockCipher,
+};
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
 pub enum CipherMode {
     ECB,
     CBC,
+    CTR,
+    CFB1,
+    CFB8,
+    CFB128,
+    OFB,
+    PCBC,
+    XTS,
+}
+
+impl CipherMode {
+    pub const ALL: [CipherMode; 9] = [
+        CipherMode::ECB,
+        CipherMode::CBC,
+        CipherMode::CTR,
+        CipherMode::CFB1,
+        CipherMode::CFB8,
+        CipherMode::CFB128,
+        CipherMode::OFB,
+        CipherMode::PCBC,
+        CipherMode::XTS,
+    ];
+
+    /// Key length in bytes for the AES-128 based variant of this mode.
+    pub fn key_size(&self) -> usize {
+        match self {
+            CipherMode::XTS => 32,
+            _ => 16,
+        }
+    }
 }
 
 pub trait Xor<T> {
@@ -25,8 +49,10 @@ pub trait RepeatingKeyXor {
     fn encrypt_repeating_key_xor(self, key: &[u8]) -> Vec<u8>;
 }
 
-pub trait Hex {
+pub trait Hex: Sized {
     fn from_hex(str: &str) -> Self;
+    /// Like `from_hex`, but returns `None` instead of panicking on bad input.
+    fn try_from_hex(str: &str) -> Option<Self>;
     fn to_hex(&self) -> String;
 }
 
@@ -52,6 +78,11 @@ impl Hex for Vec<u8> {
         bytes
     }
 
+    fn try_from_hex(str: &str) -> Option<Self> {
+        let valid = str.len().is_multiple_of(2) && str.bytes().all(|b| b.is_ascii_hexdigit());
+        valid.then(|| Self::from_hex(str))
+    }
+
     fn to_hex(&self) -> String {
         let mut str = String::new();
         for byte in self.clone().iter() {
@@ -61,9 +92,11 @@ impl Hex for Vec<u8> {
     }
 }
 
-pub trait Base64 {
+pub trait Base64: Sized {
     fn to_base64(&self) -> String;
     fn from_base64(str: &str) -> Self;
+    /// Like `from_base64`, but returns `None` instead of panicking on bad input.
+    fn try_from_base64(str: &str) -> Option<Self>;
 }
 
 impl Base64 for Vec<u8> {
@@ -75,6 +108,11 @@ impl Base64 for Vec<u8> {
         let r = str.replace("\n", "").replace("\r", "");
         general_purpose::STANDARD.decode(r).unwrap()
     }
+
+    fn try_from_base64(str: &str) -> Option<Self> {
+        let r = str.replace("\n", "").replace("\r", "");
+        general_purpose::STANDARD.decode(r).ok()
+    }
 }
 
 impl Xor<Vec<u8>> for &mut [u8] {
@@ -114,44 +152,19 @@ impl DetectDuplicate for &[u8] {
 }
 
 pub fn decrypt_aes_128(input: &[u8], key: &[u8]) -> Vec<u8> {
-    let mut output = vec![];
-    let key = GenericArray::clone_from_slice(key);
-    (0..input.len()).step_by(16).for_each(|block| {
-        let mut chunk = GenericArray::clone_from_slice(&input[block..block + 16]);
-        let cipher = Aes128::new(&key);
-        cipher.decrypt_block(&mut chunk);
-        output.extend_from_slice(&chunk);
-    });
-    output
+    ecb_decrypt(&Aes128::new(key), input)
 }
 
 pub fn decrypt_aes_128_padded(input: &[u8], key: &[u8]) -> Vec<u8> {
-    let mut output = vec![];
-    let key = GenericArray::clone_from_slice(key);
-    (0..input.len()).step_by(16).for_each(|block| {
-        let mut chunk = GenericArray::clone_from_slice(&input[block..block + 16]);
-        let cipher = Aes128::new(&key);
-        cipher.decrypt_block(&mut chunk);
-        output.extend_from_slice(&chunk);
-    });
-    pkcs7::strip_padding(output).expect("Invalid padding")
+    padded_ecb_decrypt(&Aes128::new(key), input).expect("Invalid padding")
 }
 
 pub fn encrypt_aes_128(input: &[u8], key: &[u8]) -> Vec<u8> {
-    let mut output = vec![];
-    let key = GenericArray::clone_from_slice(key);
-    (0..input.len()).step_by(16).for_each(|block| {
-        let mut chunk = GenericArray::clone_from_slice(&input[block..block + 16]);
-        let cipher = Aes128::new(&key);
-        cipher.encrypt_block(&mut chunk);
-        output.extend_from_slice(&chunk);
-    });
-    output
+    ecb_encrypt(&Aes128::new(key), input)
 }
 
 pub fn padded_encrypt_aes_128(input: &[u8], key: &[u8]) -> Vec<u8> {
-    let padded = pkcs7::pad_to_blocksize(input.to_vec(), 16);
-    encrypt_aes_128(&padded, key)
+    padded_ecb_encrypt(&Aes128::new(key), input)
 }
 
 pub fn detect_cbc_or_ecb(input: &[u8]) -> CipherMode {
@@ -162,6 +175,85 @@ pub fn detect_cbc_or_ecb(input: &[u8]) -> CipherMode {
     }
 }
 
+/// Which blocks of a ciphertext repeat, and how surprising that would be if the
+/// ciphertext were uniformly random.
+#[derive(Debug, PartialEq)]
+pub struct EcbReport {
+    pub block_size: usize,
+    pub offset: usize,
+    /// Number of whole blocks examined after `offset`.
+    pub blocks: usize,
+    /// Block indices of each distinct block that occurs more than once.
+    pub repeats: Vec<Vec<usize>>,
+    /// How many pairs of examined blocks are equal.
+    pub colliding_pairs: usize,
+    /// Chance of at least `colliding_pairs` collisions among random blocks.
+    pub random_probability: f64,
+}
+
+impl EcbReport {
+    /// Whether the repeats are too unlikely to be chance at `significance`.
+    pub fn is_ecb(&self, significance: f64) -> bool {
+        self.colliding_pairs > 0 && self.random_probability < significance
+    }
+}
+
+/// Splits `input[offset..]` into `block_size` blocks and reports the repeats.
+///
+/// Collisions between random blocks are close to Poisson distributed, with a
+/// mean of one per 2^(8 * block_size) pairs.
+///
+/// Panics if `block_size` is zero.
+pub fn ecb_report(input: &[u8], block_size: usize, offset: usize) -> EcbReport {
+    assert!(block_size > 0, "ecb_report needs a non-zero block size");
+    let blocks = input
+        .get(offset..)
+        .unwrap_or_default()
+        .chunks_exact(block_size)
+        .collect_vec();
+
+    let mut positions: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    for (i, block) in blocks.iter().enumerate() {
+        positions.entry(block).or_default().push(i);
+    }
+    let repeats = positions
+        .into_values()
+        .filter(|indices| indices.len() > 1)
+        .sorted_by_key(|indices| indices[0])
+        .collect_vec();
+    let colliding_pairs = repeats
+        .iter()
+        .map(|indices| indices.len() * (indices.len() - 1) / 2)
+        .sum::<usize>();
+
+    let n = blocks.len() as f64;
+    let mean = n * (n - 1.) / 2. / 2f64.powi(8 * block_size as i32);
+    let mut random_probability = 1.;
+    if colliding_pairs > 0 {
+        // sum the upper tail directly, since 1 - P(X < k) rounds to zero for
+        // large blocks, and in log space so a large mean doesn't underflow
+        let k = colliding_pairs as f64;
+        let log_factorial = (1..=colliding_pairs).map(|i| (i as f64).ln()).sum::<f64>();
+        let mut log_term = -mean + k * mean.ln() - log_factorial;
+        let mut i = k;
+        random_probability = 0.;
+        while i < k + 64. || i < mean + 64. * mean.sqrt() {
+            random_probability += log_term.exp();
+            i += 1.;
+            log_term += (mean / i).ln();
+        }
+    }
+
+    EcbReport {
+        block_size,
+        offset,
+        blocks: blocks.len(),
+        repeats,
+        colliding_pairs,
+        random_probability: random_probability.min(1.),
+    }
+}
+
 pub fn random_key(size: usize) -> Vec<u8> {
     (0..size).map(|_| rand::random::<u8>()/2).collect()
 }
@@ -176,6 +268,63 @@ pub fn safe_string(input: &[u8]) -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_ecb_report() {
+        let report = std::fs::read_to_string("data/8.txt")
+            .unwrap()
+            .lines()
+            .map(|line| ecb_report(&Vec::<u8>::from_hex(line), 16, 0))
+            .enumerate()
+            .filter(|(_, report)| report.is_ecb(1e-6))
+            .collect_vec();
+        assert_eq!(report.len(), 1);
+        let (line, report) = &report[0];
+        assert_eq!(*line, 132);
+        assert_eq!(report.blocks, 10);
+        assert_eq!(report.repeats, vec![vec![1, 3, 5, 7]]);
+        assert_eq!(report.colliding_pairs, 6);
+        assert!(report.random_probability < 1e-200);
+    }
+
+    #[test]
+    fn test_ecb_report_offset_and_block_size() {
+        let input = [b"xyz".as_slice(), &b"ABCDEFGH".repeat(2), b"12345678", b"ABCDEFGH", b"tail"]
+            .concat();
+        let aligned = ecb_report(&input, 8, 3);
+        assert_eq!(aligned.blocks, 4);
+        assert_eq!(aligned.repeats, vec![vec![0, 1, 3]]);
+        assert_eq!(aligned.colliding_pairs, 3);
+
+        let misaligned = ecb_report(&input, 8, 0);
+        assert!(misaligned.repeats.is_empty());
+        assert_eq!(misaligned.random_probability, 1.);
+        assert!(!misaligned.is_ecb(0.5));
+
+        // short random blocks collide all the time, which isn't evidence of ECB
+        let random = (0..4096).map(|_| rand::random::<u8>()).collect_vec();
+        for block_size in [1, 2] {
+            let noisy = ecb_report(&random, block_size, 0);
+            assert!(noisy.colliding_pairs > 0);
+            assert!(!noisy.is_ecb(1e-6));
+        }
+        assert!(ecb_report(&input, 8, 100).repeats.is_empty());
+    }
+
+    #[test]
+    fn test_ecb_report_orders_repeats_by_first_block() {
+        let input = b"zzyyxxyyzzxxww".to_vec();
+        assert_eq!(
+            ecb_report(&input, 2, 0).repeats,
+            vec![vec![0, 4], vec![1, 3], vec![2, 5]]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "non-zero block size")]
+    fn test_ecb_report_zero_block_size() {
+        ecb_report(b"YELLOW SUBMARINE", 0, 0);
+    }
+
     #[test]
     fn test_xor() {
         let mut hex1 = Vec::<u8>::from_hex("1c0111001f010100061a024b53535009181c");
@@ -217,5 +366,7 @@ I go crazy when I hear a cymbal"
 I go crazy when I hear a cymbal"
             .as_bytes();
         let key = "YELLOW SUBMARINE".as_bytes();
+        let encrypted = padded_encrypt_aes_128(input, key);
+        assert_eq!(decrypt_aes_128_padded(&encrypted, key), input);
     }
 }